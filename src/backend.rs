@@ -0,0 +1,138 @@
+// Copyright 2023 Krzysztof Wisniewski <argmaster.world@gmail.com>
+//
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy of this
+// software and associated documentation files (the “Software”), to deal in the Software
+// without restriction, including without limitation the rights to use, copy, modify,
+// merge, publish, distribute, sublicense, and/or sell copies of the Software, and to
+// permit persons to whom the Software is furnished to do so, subject to the following
+// conditions:
+//
+// The above copyright notice and this permission notice shall be included in all copies
+// or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED “AS IS”, WITHOUT WARRANTY OF ANY KIND, EXPRESS OR IMPLIED,
+// INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A
+// PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT
+// HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION OF
+// CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE
+// OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+
+//! Interface of Gilbert algorithm implementations, allowing them to be used (and
+//! exposed to Python) interchangeably.
+
+use std::fs;
+use std::ops::ControlFlow;
+use std::path::Path;
+
+use ndarray as nd;
+use num::Complex;
+
+use crate::error::{Error, Result};
+use crate::shared::{AlgoMode, SymmetryMode};
+
+/// Progress of running epoch, reported by `Backend::run_epoch_with`.
+#[derive(Clone, Copy, Debug)]
+pub struct Progress<T> {
+    /// Number of iterations of the epoch completed so far.
+    pub iteration: i64,
+    /// Total number of corrections found by the backend.
+    pub corrections: usize,
+    /// Value of the latest correction, squared Hilbert-Schmidt distance between
+    /// visibility state and intermediate state, `None` before first correction.
+    pub distance: Option<T>,
+}
+
+/// Implementation of Gilbert algorithm searching for the closest separable state,
+/// operating on complex matrices with elements of precision `T`.
+pub trait Backend<T>: Sized {
+    /// Create backend for `initial` state composed of subsystems with dimensions
+    /// given by `dimensions`. Backend seeded with the same `seed` produces the same
+    /// corrections, without seed it draws seed from system entropy.
+    fn new(
+        initial: &nd::ArrayView2<Complex<T>>,
+        dimensions: &[usize],
+        mode: AlgoMode,
+        visibility: T,
+        seed: Option<u64>,
+    ) -> Result<Self>;
+
+    /// Set symmetries applied to every optimized candidate, see `SymmetryMode`.
+    fn set_symmetries(
+        &mut self,
+        symmetries: Vec<Vec<nd::Array2<Complex<T>>>>,
+        mode: SymmetryMode,
+    ) -> Result<()>;
+
+    /// Set projection applied to every optimized candidate.
+    fn set_projection(&mut self, projection: nd::Array2<Complex<T>>) -> Result<()>;
+
+    /// Set number of threads used by the backend, backends not supporting multiple
+    /// threads accept only 1.
+    fn set_threads(&mut self, threads: usize) -> Result<()> {
+        match threads {
+            1 => Ok(()),
+            _ => Err(Error::NotImplemented(
+                "Backend does not support multiple threads.".to_string(),
+            )),
+        }
+    }
+
+    fn get_threads(&self) -> usize {
+        1
+    }
+
+    fn get_dimensions(&self) -> &[usize];
+
+    fn get_state(&self) -> &nd::Array2<Complex<T>>;
+
+    fn get_corrections(&self) -> &[(usize, usize, T)];
+
+    /// Run epoch of `iterations` iterations, `epoch_index` is index of this epoch among
+    /// all epochs run by the backend.
+    fn run_epoch(&mut self, iterations: i64, epoch_index: usize) {
+        let _ = self.run_epoch_with(iterations, epoch_index, iterations.max(1), |_| {
+            ControlFlow::<()>::Continue(())
+        });
+    }
+
+    /// Run epoch of `iterations` iterations, calling `callback` every `interval`
+    /// iterations with progress of the epoch. When `callback` returns
+    /// `ControlFlow::Break` epoch stops immediately, leaving backend in consistent
+    /// state, and break value is returned.
+    fn run_epoch_with<B, F>(
+        &mut self,
+        iterations: i64,
+        epoch_index: usize,
+        interval: i64,
+        callback: F,
+    ) -> ControlFlow<B>
+    where
+        F: FnMut(Progress<T>) -> ControlFlow<B>;
+
+    /// Serialize complete state of the backend, backend restored with `from_bytes`
+    /// continues exactly as this one would.
+    fn to_bytes(&self) -> Result<Vec<u8>> {
+        Err(Error::NotImplemented(
+            "Backend does not support serialization.".to_string(),
+        ))
+    }
+
+    /// Restore backend from snapshot created with `to_bytes`.
+    fn from_bytes(_bytes: &[u8]) -> Result<Self> {
+        Err(Error::NotImplemented(
+            "Backend does not support serialization.".to_string(),
+        ))
+    }
+
+    /// Write snapshot of the backend to file at `path`, see `to_bytes`.
+    fn save_checkpoint<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        fs::write(path, self.to_bytes()?)?;
+        Ok(())
+    }
+
+    /// Restore backend from file written with `save_checkpoint`.
+    fn load_checkpoint<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Self::from_bytes(&fs::read(path)?)
+    }
+}
@@ -0,0 +1,550 @@
+// Copyright 2023 Krzysztof Wisniewski <argmaster.world@gmail.com>
+//
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy of this
+// software and associated documentation files (the “Software”), to deal in the Software
+// without restriction, including without limitation the rights to use, copy, modify,
+// merge, publish, distribute, sublicense, and/or sell copies of the Software, and to
+// permit persons to whom the Software is furnished to do so, subject to the following
+// conditions:
+//
+// The above copyright notice and this permission notice shall be included in all copies
+// or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED “AS IS”, WITHOUT WARRANTY OF ANY KIND, EXPRESS OR IMPLIED,
+// INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A
+// PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT
+// HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION OF
+// CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE
+// OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+
+//! Command line runner of CSSFinder tasks, runs `RustBackend` without Python.
+//!
+//! Density matrices are read from Matrix Market files (`.mtx`) or, with any other
+//! extension, from plain text, one row per line, with elements separated by whitespace
+//! and written as complex numbers, eg. `0.5-0.5i`. Final state is written in the same
+//! format as initial one.
+
+use std::fmt::{Display, LowerExp};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::str::FromStr;
+
+use cssfinder_backend_rust::backend::Backend;
+use cssfinder_backend_rust::error::{Error, Result};
+use cssfinder_backend_rust::mtx::{self, MtxFormat};
+use cssfinder_backend_rust::naive::RustBackend;
+use cssfinder_backend_rust::shared::AlgoMode;
+use ndarray as nd;
+use num::{Complex, Float};
+
+const USAGE: &str = "\
+Usage: cssfinder-rs [OPTIONS] --input <FILE> --output <DIR>
+
+Run Gilbert algorithm on density matrix from <FILE> and write final state
+(state.mtx for Matrix Market input, state.txt otherwise) and corrections
+(corrections.json) into <DIR>.
+
+Options:
+    --input <FILE>         Initial density matrix, .mtx or plain text.
+    --output <DIR>         Directory for results, created if missing.
+    --mode <MODE>          FSnQd, SBiPa, G3PaE3qD or G4PaE3qD. [default: FSnQd]
+    --visibility <VALUE>   Visibility of initial state, from 0 to 1. [default: 0.4]
+    --depth <N>            Dimension of single subsystem. [default: 2]
+    --quantity <N>         Number of subsystems, in SBiPa mode dimension of second
+                           subsystem. [default: 2]
+    --epochs <N>           Number of epochs. [default: 1]
+    --iterations <N>       Number of iterations per epoch. [default: 1000]
+    --threads <N>          Number of threads. [default: 1]
+    --seed <N>             Seed of random number generator, random if omitted.
+    --precision <NAME>     single or double. [default: double]
+    -h, --help             Print this message.
+";
+
+/// Precision of complex numbers used by backend, named after Python `Precision` enum.
+#[derive(Clone, Copy, Debug)]
+enum Precision {
+    Single,
+    Double,
+}
+
+#[derive(Debug)]
+struct Options {
+    input: PathBuf,
+    output: PathBuf,
+    mode: AlgoMode,
+    visibility: f64,
+    depth: usize,
+    quantity: usize,
+    epochs: usize,
+    iterations: i64,
+    threads: usize,
+    seed: Option<u64>,
+    precision: Precision,
+}
+
+/// Parse command line arguments, `None` when help was requested.
+fn parse_args<I>(mut args: I) -> Result<Option<Options>>
+where
+    I: Iterator<Item = String>,
+{
+    let mut input = None;
+    let mut output = None;
+    let mut options = Options {
+        input: PathBuf::new(),
+        output: PathBuf::new(),
+        mode: AlgoMode::FSnQd,
+        visibility: 0.4,
+        depth: 2,
+        quantity: 2,
+        epochs: 1,
+        iterations: 1000,
+        threads: 1,
+        seed: None,
+        precision: Precision::Double,
+    };
+
+    while let Some(flag) = args.next() {
+        if flag == "-h" || flag == "--help" {
+            return Ok(None);
+        }
+        let value = args
+            .next()
+            .ok_or_else(|| Error::Value(format!("Missing value of {}.", flag)))?;
+        match flag.as_str() {
+            "--input" => input = Some(PathBuf::from(value)),
+            "--output" => output = Some(PathBuf::from(value)),
+            "--mode" => {
+                options.mode = AlgoMode::from_name(&value)
+                    .ok_or_else(|| Error::Value(format!("Unknown mode {}.", value)))?
+            }
+            "--visibility" => options.visibility = parse_value(&flag, &value)?,
+            "--depth" => options.depth = parse_value(&flag, &value)?,
+            "--quantity" => options.quantity = parse_value(&flag, &value)?,
+            "--epochs" => options.epochs = parse_value(&flag, &value)?,
+            "--iterations" => options.iterations = parse_value(&flag, &value)?,
+            "--threads" => options.threads = parse_value(&flag, &value)?,
+            "--seed" => options.seed = Some(parse_value(&flag, &value)?),
+            "--precision" => {
+                options.precision = match value.as_str() {
+                    "single" => Precision::Single,
+                    "double" => Precision::Double,
+                    _ => {
+                        return Err(Error::Value(format!(
+                            "Unknown precision {}.",
+                            value
+                        )))
+                    }
+                }
+            }
+            _ => return Err(Error::Value(format!("Unknown option {}.", flag))),
+        }
+    }
+
+    if options.iterations < 0 {
+        return Err(Error::Value(format!(
+            "Invalid value of --iterations: {}, has to be non-negative.",
+            options.iterations
+        )));
+    }
+    if !(0.0..=1.0).contains(&options.visibility) {
+        return Err(Error::Value(format!(
+            "Invalid value of --visibility: {}, has to be in range [0, 1].",
+            options.visibility
+        )));
+    }
+    options.input =
+        input.ok_or_else(|| Error::Value("Missing --input.".to_string()))?;
+    options.output =
+        output.ok_or_else(|| Error::Value("Missing --output.".to_string()))?;
+    Ok(Some(options))
+}
+
+fn parse_value<V: FromStr>(flag: &str, value: &str) -> Result<V> {
+    value
+        .parse()
+        .map_err(|_| Error::Value(format!("Invalid value of {}: {}.", flag, value)))
+}
+
+/// Whether `path` refers to Matrix Market file, otherwise plain text is used.
+fn is_mtx(path: &Path) -> bool {
+    path.extension()
+        .map_or(false, |extension| extension.eq_ignore_ascii_case("mtx"))
+}
+
+/// Read square density matrix from text file.
+fn load_matrix<T>(path: &Path) -> Result<nd::Array2<Complex<T>>>
+where
+    T: Float + FromStr,
+{
+    let content = fs::read_to_string(path)
+        .map_err(|error| Error::Io(format!("{}: {}", path.display(), error)))?;
+
+    let mut rows = 0;
+    let mut columns = None;
+    let mut elements = vec![];
+    for (line_index, line) in content.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let before = elements.len();
+        for element in line.split_whitespace() {
+            elements.push(element.parse::<Complex<T>>().map_err(|_| {
+                Error::Value(format!(
+                    "{}:{}: Invalid complex number {}.",
+                    path.display(),
+                    line_index + 1,
+                    element
+                ))
+            })?);
+        }
+        let length = elements.len() - before;
+        if *columns.get_or_insert(length) != length {
+            return Err(Error::Value(format!(
+                "{}:{}: Expected {} elements in row, got {}.",
+                path.display(),
+                line_index + 1,
+                columns.unwrap_or_default(),
+                length
+            )));
+        }
+        rows += 1;
+    }
+
+    let columns = columns.unwrap_or_default();
+    if rows != columns {
+        return Err(Error::Value(format!(
+            "{}: Expected square matrix, got {}x{}.",
+            path.display(),
+            rows,
+            columns
+        )));
+    }
+    Ok(nd::Array2::from_shape_vec((rows, columns), elements)?)
+}
+
+/// Write matrix to text file in format accepted by `load_matrix`.
+fn save_matrix<T>(path: &Path, matrix: &nd::Array2<Complex<T>>) -> Result<()>
+where
+    T: Float + Display,
+{
+    let content = matrix
+        .rows()
+        .into_iter()
+        .map(|row| {
+            let row = row.iter().map(|v| v.to_string()).collect::<Vec<_>>();
+            row.join(" ") + "\n"
+        })
+        .collect::<String>();
+    Ok(fs::write(path, content)?)
+}
+
+/// Write corrections as JSON list of objects with `iteration`, `index` and `value`.
+fn save_corrections<T>(path: &Path, corrections: &[(usize, usize, T)]) -> Result<()>
+where
+    T: Display,
+{
+    let content = corrections
+        .iter()
+        .map(|(iteration, index, value)| {
+            format!(
+                "  {{\"iteration\": {}, \"index\": {}, \"value\": {}}}",
+                iteration, index, value
+            )
+        })
+        .collect::<Vec<_>>();
+    Ok(fs::write(path, format!("[\n{}\n]\n", content.join(",\n")))?)
+}
+
+fn run<T>(options: &Options) -> Result<()>
+where
+    T: Float
+        + std::fmt::Debug
+        + rand_distr::uniform::SampleUniform
+        + Display
+        + LowerExp
+        + FromStr
+        + Send
+        + Sync
+        + 'static,
+{
+    let initial = if is_mtx(&options.input) {
+        mtx::load_mtx::<T, _>(&options.input)?
+    } else {
+        load_matrix::<T>(&options.input)?
+    };
+    let dimensions = options.mode.dimensions(options.depth, options.quantity);
+    let visibility = T::from(options.visibility).unwrap();
+
+    let mut backend = <RustBackend<T> as Backend<T>>::new(
+        &initial.view(),
+        &dimensions,
+        options.mode,
+        visibility,
+        options.seed,
+    )?;
+    backend.set_threads(options.threads)?;
+
+    for epoch_index in 0..options.epochs {
+        backend.run_epoch(options.iterations, epoch_index);
+        let corrections = backend.get_corrections();
+        match corrections.last() {
+            Some((_, _, distance)) => eprintln!(
+                "Epoch {}/{}: {} corrections, distance {}.",
+                epoch_index + 1,
+                options.epochs,
+                corrections.len(),
+                distance
+            ),
+            None => eprintln!(
+                "Epoch {}/{}: no corrections.",
+                epoch_index + 1,
+                options.epochs
+            ),
+        }
+    }
+
+    fs::create_dir_all(&options.output)?;
+    if is_mtx(&options.input) {
+        mtx::save_mtx(
+            options.output.join("state.mtx"),
+            backend.get_state(),
+            MtxFormat::Array,
+        )?;
+    } else {
+        save_matrix(&options.output.join("state.txt"), backend.get_state())?;
+    }
+    save_corrections(
+        &options.output.join("corrections.json"),
+        backend.get_corrections(),
+    )
+}
+
+fn main() -> ExitCode {
+    let options = match parse_args(std::env::args().skip(1)) {
+        Ok(Some(options)) => options,
+        Ok(None) => {
+            print!("{}", USAGE);
+            return ExitCode::SUCCESS;
+        }
+        Err(error) => {
+            eprintln!("Error: {}\n\n{}", error, USAGE);
+            return ExitCode::from(2);
+        }
+    };
+
+    let result = match options.precision {
+        Precision::Single => run::<f32>(&options),
+        Precision::Double => run::<f64>(&options),
+    };
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(error) => {
+            eprintln!("Error: {}", error);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Option<Options>> {
+        parse_args(args.iter().map(|arg| arg.to_string()))
+    }
+
+    /// Path of file in temporary directory, unique for test `name`.
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!(
+            "cssfinder-rs-{}-{}",
+            std::process::id(),
+            name
+        ))
+    }
+
+    fn load_text(name: &str, content: &str) -> Result<nd::Array2<Complex<f64>>> {
+        let path = temp_path(name);
+        fs::write(&path, content).unwrap();
+        let result = load_matrix::<f64>(&path);
+        fs::remove_file(&path).unwrap();
+        result
+    }
+
+    #[test]
+    fn parse_args_defaults() {
+        let options = parse(&["--input", "in.txt", "--output", "out"])
+            .unwrap()
+            .unwrap();
+        assert_eq!(options.input, PathBuf::from("in.txt"));
+        assert_eq!(options.output, PathBuf::from("out"));
+        assert_eq!(options.mode, AlgoMode::FSnQd);
+        assert_eq!(options.visibility, 0.4);
+        assert_eq!((options.depth, options.quantity), (2, 2));
+        assert_eq!(
+            (options.epochs, options.iterations, options.threads),
+            (1, 1000, 1)
+        );
+        assert_eq!(options.seed, None);
+        assert!(matches!(options.precision, Precision::Double));
+    }
+
+    #[test]
+    fn parse_args_all_options() {
+        let options = parse(&[
+            "--input",
+            "in.mtx",
+            "--output",
+            "out",
+            "--mode",
+            "SBiPa",
+            "--visibility",
+            "1",
+            "--depth",
+            "3",
+            "--quantity",
+            "4",
+            "--epochs",
+            "5",
+            "--iterations",
+            "0",
+            "--threads",
+            "6",
+            "--seed",
+            "7",
+            "--precision",
+            "single",
+        ])
+        .unwrap()
+        .unwrap();
+        assert_eq!(options.mode, AlgoMode::SBiPa);
+        assert_eq!(options.visibility, 1.0);
+        assert_eq!((options.depth, options.quantity), (3, 4));
+        assert_eq!(
+            (options.epochs, options.iterations, options.threads),
+            (5, 0, 6)
+        );
+        assert_eq!(options.seed, Some(7));
+        assert!(matches!(options.precision, Precision::Single));
+    }
+
+    #[test]
+    fn parse_args_help() {
+        assert!(parse(&["--input", "in.txt", "--help"]).unwrap().is_none());
+        assert!(parse(&["-h"]).unwrap().is_none());
+    }
+
+    #[test]
+    fn parse_args_invalid() {
+        for args in [
+            &["--output", "out"][..],
+            &["--input", "in.txt"],
+            &["--input", "in.txt", "--output"],
+            &["--input", "in.txt", "--output", "out", "--unknown", "1"],
+            &["--input", "in.txt", "--output", "out", "--mode", "FSnQ"],
+            &[
+                "--input",
+                "in.txt",
+                "--output",
+                "out",
+                "--precision",
+                "half",
+            ],
+            &["--input", "in.txt", "--output", "out", "--depth", "two"],
+            &["--input", "in.txt", "--output", "out", "--iterations", "-5"],
+            &[
+                "--input",
+                "in.txt",
+                "--output",
+                "out",
+                "--visibility",
+                "-0.1",
+            ],
+            &[
+                "--input",
+                "in.txt",
+                "--output",
+                "out",
+                "--visibility",
+                "1.5",
+            ],
+            &[
+                "--input",
+                "in.txt",
+                "--output",
+                "out",
+                "--visibility",
+                "NaN",
+            ],
+        ] {
+            assert!(
+                matches!(parse(args), Err(Error::Value(_))),
+                "Accepted {:?}",
+                args
+            );
+        }
+    }
+
+    #[test]
+    fn is_mtx_extension() {
+        assert!(is_mtx(Path::new("state.mtx")));
+        assert!(is_mtx(Path::new("state.MTX")));
+        assert!(!is_mtx(Path::new("state.txt")));
+        assert!(!is_mtx(Path::new("mtx")));
+    }
+
+    #[test]
+    fn save_and_load_matrix() {
+        let matrix = nd::array![
+            [Complex::new(0.5, 0.0), Complex::new(0.25, -0.5)],
+            [Complex::new(0.25, 0.5), Complex::new(0.5, 0.0)]
+        ];
+        let path = temp_path("roundtrip.txt");
+        save_matrix(&path, &matrix).unwrap();
+        let loaded = load_matrix::<f64>(&path);
+        fs::remove_file(&path).unwrap();
+        assert_eq!(loaded.unwrap(), matrix);
+    }
+
+    #[test]
+    fn load_matrix_text() {
+        let loaded = load_text("text.txt", "1 0.5-0.5i\n\n0.5+0.5i  1\n").unwrap();
+        assert_eq!(
+            loaded,
+            nd::array![
+                [Complex::new(1.0, 0.0), Complex::new(0.5, -0.5)],
+                [Complex::new(0.5, 0.5), Complex::new(1.0, 0.0)]
+            ]
+        );
+    }
+
+    #[test]
+    fn load_matrix_ragged() {
+        let error = load_text("ragged.txt", "1 0\n0\n").unwrap_err();
+        assert!(matches!(&error, Error::Value(message)
+            if message.ends_with(":2: Expected 2 elements in row, got 1.")));
+    }
+
+    #[test]
+    fn load_matrix_not_square() {
+        let error = load_text("rectangular.txt", "1 0 0\n0 1 0\n").unwrap_err();
+        assert!(matches!(&error, Error::Value(message)
+            if message.ends_with(": Expected square matrix, got 2x3.")));
+    }
+
+    #[test]
+    fn load_matrix_invalid_number() {
+        let error = load_text("invalid.txt", "1 x\n0 1\n").unwrap_err();
+        assert!(matches!(&error, Error::Value(message)
+            if message.ends_with(":1: Invalid complex number x.")));
+    }
+
+    #[test]
+    fn load_matrix_missing_file() {
+        assert!(matches!(
+            load_matrix::<f64>(&temp_path("missing.txt")),
+            Err(Error::Io(_))
+        ));
+    }
+}
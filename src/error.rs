@@ -0,0 +1,86 @@
+// Copyright 2023 Krzysztof Wisniewski <argmaster.world@gmail.com>
+//
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy of this
+// software and associated documentation files (the “Software”), to deal in the Software
+// without restriction, including without limitation the rights to use, copy, modify,
+// merge, publish, distribute, sublicense, and/or sell copies of the Software, and to
+// permit persons to whom the Software is furnished to do so, subject to the following
+// conditions:
+//
+// The above copyright notice and this permission notice shall be included in all copies
+// or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED “AS IS”, WITHOUT WARRANTY OF ANY KIND, EXPRESS OR IMPLIED,
+// INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A
+// PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT
+// HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION OF
+// CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE
+// OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+
+use std::fmt;
+
+use ndarray as nd;
+#[cfg(feature = "python")]
+use pyo3::exceptions::{
+    PyImportError, PyNotImplementedError, PyOSError, PyTypeError, PyValueError,
+};
+#[cfg(feature = "python")]
+use pyo3::PyErr;
+
+/// Errors raised by the backend, with `python` feature each maps to Python exception of
+/// similar meaning.
+#[derive(Clone, Debug)]
+pub enum Error {
+    /// Argument has correct type but invalid value, eg. array of wrong shape.
+    Value(String),
+    /// Argument has type which can not be used.
+    Type(String),
+    /// Module required at runtime could not be imported.
+    Import(String),
+    /// Requested functionality is not available in this backend.
+    NotImplemented(String),
+    /// Reading or writing file failed.
+    Io(String),
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Value(message)
+            | Error::Type(message)
+            | Error::Import(message)
+            | Error::NotImplemented(message)
+            | Error::Io(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<nd::ShapeError> for Error {
+    fn from(error: nd::ShapeError) -> Self {
+        Error::Value(error.to_string())
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(error: std::io::Error) -> Self {
+        Error::Io(error.to_string())
+    }
+}
+
+#[cfg(feature = "python")]
+impl From<Error> for PyErr {
+    fn from(error: Error) -> Self {
+        match error {
+            Error::Value(message) => PyValueError::new_err(message),
+            Error::Type(message) => PyTypeError::new_err(message),
+            Error::Import(message) => PyImportError::new_err(message),
+            Error::NotImplemented(message) => PyNotImplementedError::new_err(message),
+            Error::Io(message) => PyOSError::new_err(message),
+        }
+    }
+}
@@ -0,0 +1,530 @@
+// Copyright 2023 Krzysztof Wisniewski <argmaster.world@gmail.com>
+//
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy of this
+// software and associated documentation files (the “Software”), to deal in the Software
+// without restriction, including without limitation the rights to use, copy, modify,
+// merge, publish, distribute, sublicense, and/or sell copies of the Software, and to
+// permit persons to whom the Software is furnished to do so, subject to the following
+// conditions:
+//
+// The above copyright notice and this permission notice shall be included in all copies
+// or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED “AS IS”, WITHOUT WARRANTY OF ANY KIND, EXPRESS OR IMPLIED,
+// INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A
+// PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT
+// HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION OF
+// CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE
+// OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+
+//! Reading and writing square complex matrices in Matrix Market exchange format, used
+//! by CSSFinder projects to store states.
+//!
+//! Both `coordinate` and `array` formats are supported. Elements can be `complex`,
+//! `real` or `integer` (the latter two are read with zero imaginary part) and stored
+//! as `general`, `symmetric`, `skew-symmetric` or `hermitian` matrix. Matrices are
+//! always written with `complex` `general` elements.
+
+use std::fmt::LowerExp;
+use std::fs;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+use std::str::FromStr;
+
+use ndarray as nd;
+use num::{Complex, Float, Zero};
+
+use crate::error::{Error, Result};
+use crate::naive::ensure_square;
+
+const BANNER: &str = "%%MatrixMarket";
+
+/// Layout of elements in Matrix Market file.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MtxFormat {
+    /// Only non-zero elements, each along with its row and column.
+    Coordinate,
+    /// All elements in column-major order.
+    Array,
+}
+
+impl MtxFormat {
+    /// Name of the format as used in Matrix Market header.
+    pub fn name(&self) -> &'static str {
+        match self {
+            MtxFormat::Coordinate => "coordinate",
+            MtxFormat::Array => "array",
+        }
+    }
+
+    /// Inverse of `name()`, `None` when name does not refer to any supported format.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "coordinate" => Some(MtxFormat::Coordinate),
+            "array" => Some(MtxFormat::Array),
+            _ => None,
+        }
+    }
+}
+
+/// Type of elements declared in header.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Field {
+    Real,
+    Complex,
+    Integer,
+}
+
+impl Field {
+    fn name(&self) -> &'static str {
+        match self {
+            Field::Real => "real",
+            Field::Complex => "complex",
+            Field::Integer => "integer",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "real" => Some(Field::Real),
+            "complex" => Some(Field::Complex),
+            "integer" => Some(Field::Integer),
+            _ => None,
+        }
+    }
+
+    /// Number of values making up single element.
+    fn width(&self) -> usize {
+        match self {
+            Field::Complex => 2,
+            Field::Real | Field::Integer => 1,
+        }
+    }
+}
+
+/// Structure of matrix declared in header, for anything other than general only
+/// lower triangle is stored.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Symmetry {
+    General,
+    Symmetric,
+    SkewSymmetric,
+    Hermitian,
+}
+
+impl Symmetry {
+    fn name(&self) -> &'static str {
+        match self {
+            Symmetry::General => "general",
+            Symmetry::Symmetric => "symmetric",
+            Symmetry::SkewSymmetric => "skew-symmetric",
+            Symmetry::Hermitian => "hermitian",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "general" => Some(Symmetry::General),
+            "symmetric" => Some(Symmetry::Symmetric),
+            "skew-symmetric" => Some(Symmetry::SkewSymmetric),
+            "hermitian" => Some(Symmetry::Hermitian),
+            _ => None,
+        }
+    }
+
+    /// Value of element mirrored over diagonal, stored only for non-general
+    /// matrices.
+    fn mirror<T: Float>(&self, value: Complex<T>) -> Complex<T> {
+        match self {
+            Symmetry::General | Symmetry::Symmetric => value,
+            Symmetry::SkewSymmetric => -value,
+            Symmetry::Hermitian => value.conj(),
+        }
+    }
+}
+
+/// Lines of Matrix Market file following header, with comments and blank lines
+/// skipped.
+struct Lines<R> {
+    lines: std::io::Lines<R>,
+    number: usize,
+}
+
+impl<R: BufRead> Lines<R> {
+    /// Next line with its number, `None` at the end of file.
+    fn next(&mut self) -> Result<Option<(usize, String)>> {
+        for line in self.lines.by_ref() {
+            let line = line?;
+            self.number += 1;
+            let trimmed = line.trim();
+            if !trimmed.is_empty() && !trimmed.starts_with('%') {
+                return Ok(Some((self.number, trimmed.to_string())));
+            }
+        }
+        Ok(None)
+    }
+}
+
+/// Parse header line, returning format, type of elements and symmetry.
+fn parse_header(line: &str) -> Result<(MtxFormat, Field, Symmetry)> {
+    let malformed = || {
+        Error::Value(format!(
+            "Expected Matrix Market header \"{} matrix <format> <field> <symmetry>\", \
+             got \"{}\".",
+            BANNER, line
+        ))
+    };
+    let tokens = line.split_whitespace().collect::<Vec<_>>();
+    let [banner, object, format, field, symmetry] = tokens[..] else {
+        return Err(malformed());
+    };
+    if banner != BANNER || !object.eq_ignore_ascii_case("matrix") {
+        return Err(malformed());
+    }
+
+    let format = MtxFormat::from_name(format).ok_or_else(|| {
+        Error::Value(format!(
+            "Expected coordinate or array Matrix Market format, got \"{}\".",
+            format
+        ))
+    })?;
+    let field = Field::from_name(field).ok_or_else(|| {
+        Error::Type(format!(
+            "Expected complex, real or integer Matrix Market elements, got \"{}\".",
+            field
+        ))
+    })?;
+    let symmetry = Symmetry::from_name(symmetry).ok_or_else(|| {
+        Error::Value(format!(
+            "Expected general, symmetric, skew-symmetric or hermitian Matrix Market \
+             matrix, got \"{}\".",
+            symmetry
+        ))
+    })?;
+    Ok((format, field, symmetry))
+}
+
+/// Parse size line holding `count` dimensions.
+fn parse_size(number: usize, line: &str, count: usize) -> Result<Vec<usize>> {
+    let size = line
+        .split_whitespace()
+        .map(|token| token.parse::<usize>().ok())
+        .collect::<Option<Vec<_>>>()
+        .filter(|size| size.len() == count);
+    size.ok_or_else(|| {
+        Error::Value(format!(
+            "Line {}: expected {} sizes in Matrix Market file, got \"{}\".",
+            number, count, line
+        ))
+    })
+}
+
+/// Parse single element made of `field.width()` values.
+fn parse_element<T>(number: usize, values: &[&str], field: Field) -> Result<Complex<T>>
+where
+    T: Float + FromStr,
+{
+    let invalid = || {
+        Error::Type(format!(
+            "Line {}: expected {} element, got \"{}\".",
+            number,
+            field.name(),
+            values.join(" ")
+        ))
+    };
+    let parse = |token: &str| match field {
+        Field::Integer => token.parse::<i64>().ok().and_then(T::from),
+        Field::Real | Field::Complex => token.parse::<T>().ok(),
+    };
+    match *values {
+        [re] if field.width() == 1 => {
+            Ok(Complex::new(parse(re).ok_or_else(invalid)?, T::zero()))
+        }
+        [re, im] if field.width() == 2 => Ok(Complex::new(
+            parse(re).ok_or_else(invalid)?,
+            parse(im).ok_or_else(invalid)?,
+        )),
+        _ => Err(invalid()),
+    }
+}
+
+/// Read square complex matrix in Matrix Market format from `reader`.
+pub fn read_mtx<T, R>(reader: R) -> Result<nd::Array2<Complex<T>>>
+where
+    T: Float + FromStr,
+    R: BufRead,
+{
+    let mut lines = reader.lines();
+    let header = lines.next().transpose()?.unwrap_or_default();
+    let (format, field, symmetry) = parse_header(header.trim())?;
+    let mut lines = Lines { lines, number: 1 };
+
+    let (number, line) = lines.next()?.ok_or_else(|| {
+        Error::Value("Matrix Market file has no size line.".to_string())
+    })?;
+    let size_count = match format {
+        MtxFormat::Coordinate => 3,
+        MtxFormat::Array => 2,
+    };
+    let size = parse_size(number, &line, size_count)?;
+    let (rows, columns) = (size[0], size[1]);
+    if rows != columns {
+        return Err(Error::Value(format!(
+            "Expected square matrix, got shape ({}, {}).",
+            rows, columns
+        )));
+    }
+    // Shape comes from file, so it has to be validated before matrix is allocated.
+    let fits = rows
+        .checked_mul(columns)
+        .and_then(|length| length.checked_mul(std::mem::size_of::<Complex<T>>()))
+        .map_or(false, |bytes| bytes <= isize::MAX as usize);
+    if !fits {
+        return Err(Error::Value(format!(
+            "Line {}: matrix of shape ({}, {}) is too large.",
+            number, rows, columns
+        )));
+    }
+
+    // Position of next element for array format, in column-major order and skipping
+    // upper triangle (and diagonal of skew-symmetric matrices) when symmetric.
+    let first_row = |column: usize| match symmetry {
+        Symmetry::General => 0,
+        Symmetry::Symmetric | Symmetry::Hermitian => column,
+        Symmetry::SkewSymmetric => column + 1,
+    };
+    let expected = match format {
+        MtxFormat::Coordinate => size[2],
+        MtxFormat::Array => (0..columns).map(|column| rows - first_row(column)).sum(),
+    };
+
+    let mut matrix = nd::Array2::<Complex<T>>::zeros((rows, columns));
+    let (mut row, mut column) = (first_row(0), 0);
+    let mut count = 0;
+    while let Some((number, line)) = lines.next()? {
+        if count == expected {
+            return Err(Error::Value(format!(
+                "Line {}: expected {} elements in Matrix Market file, got more.",
+                number, expected
+            )));
+        }
+        let tokens = line.split_whitespace().collect::<Vec<_>>();
+
+        let (i, j, values) = match format {
+            MtxFormat::Coordinate => {
+                let [i, j, values @ ..] = &tokens[..] else {
+                    return Err(Error::Value(format!(
+                        "Line {}: expected row and column of element, got \"{}\".",
+                        number, line
+                    )));
+                };
+                let index = |token: &str| {
+                    token.parse::<usize>().map_err(|_| {
+                        Error::Value(format!(
+                            "Line {}: invalid index \"{}\" in Matrix Market file.",
+                            number, token
+                        ))
+                    })
+                };
+                let (i, j) = (index(i)?, index(j)?);
+                if !(1..=rows).contains(&i) || !(1..=columns).contains(&j) {
+                    return Err(Error::Value(format!(
+                        "Line {}: index ({}, {}) out of range for matrix of shape \
+                         ({}, {}).",
+                        number, i, j, rows, columns
+                    )));
+                }
+                if i < j && symmetry != Symmetry::General {
+                    return Err(Error::Value(format!(
+                        "Line {}: element ({}, {}) above diagonal of {} matrix.",
+                        number,
+                        i,
+                        j,
+                        symmetry.name()
+                    )));
+                }
+                (i - 1, j - 1, values)
+            }
+            MtxFormat::Array => {
+                let position = (row, column);
+                row += 1;
+                if row == rows {
+                    column += 1;
+                    row = first_row(column);
+                }
+                (position.0, position.1, &tokens[..])
+            }
+        };
+
+        let element = parse_element(number, values, field)?;
+        matrix[(i, j)] = element;
+        if i != j && symmetry != Symmetry::General {
+            matrix[(j, i)] = symmetry.mirror(element);
+        }
+        count += 1;
+    }
+
+    if count != expected {
+        return Err(Error::Value(format!(
+            "Expected {} elements in Matrix Market file, got {}.",
+            expected, count
+        )));
+    }
+    Ok(matrix)
+}
+
+/// Read square complex matrix from Matrix Market file.
+pub fn load_mtx<T, P>(path: P) -> Result<nd::Array2<Complex<T>>>
+where
+    T: Float + FromStr,
+    P: AsRef<Path>,
+{
+    read_mtx(BufReader::new(fs::File::open(path)?))
+}
+
+/// Write square complex matrix to `writer` in Matrix Market format.
+pub fn write_mtx<T, S, W>(
+    writer: W,
+    matrix: &nd::ArrayBase<S, nd::Ix2>,
+    format: MtxFormat,
+) -> Result<()>
+where
+    T: Float + LowerExp,
+    S: nd::Data<Elem = Complex<T>>,
+    W: Write,
+{
+    let size = ensure_square(&matrix.view(), "Matrix")?;
+    let mut writer = BufWriter::new(writer);
+    writeln!(
+        writer,
+        "{} matrix {} complex general",
+        BANNER,
+        format.name()
+    )?;
+
+    // Elements are written in column-major order in both formats.
+    let elements = matrix.t().into_iter();
+    match format {
+        MtxFormat::Coordinate => {
+            let non_zero = matrix.iter().filter(|v| !v.is_zero()).count();
+            writeln!(writer, "{} {} {}", size, size, non_zero)?;
+            for (index, v) in elements.enumerate().filter(|(_, v)| !v.is_zero()) {
+                let (column, row) = (index / size, index % size);
+                writeln!(writer, "{} {} {:e} {:e}", row + 1, column + 1, v.re, v.im)?;
+            }
+        }
+        MtxFormat::Array => {
+            writeln!(writer, "{} {}", size, size)?;
+            for v in elements {
+                writeln!(writer, "{:e} {:e}", v.re, v.im)?;
+            }
+        }
+    }
+    Ok(writer.flush()?)
+}
+
+/// Write square complex matrix to Matrix Market file.
+pub fn save_mtx<T, S, P>(
+    path: P,
+    matrix: &nd::ArrayBase<S, nd::Ix2>,
+    format: MtxFormat,
+) -> Result<()>
+where
+    T: Float + LowerExp,
+    S: nd::Data<Elem = Complex<T>>,
+    P: AsRef<Path>,
+{
+    write_mtx(fs::File::create(path)?, matrix, format)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matrix() -> nd::Array2<Complex<f64>> {
+        nd::Array2::from_shape_fn((3, 3), |(i, j)| {
+            if (i + j) % 2 == 0 {
+                Complex::new(i as f64 + 0.25, j as f64 - 1.5)
+            } else {
+                Complex::zero()
+            }
+        })
+    }
+
+    fn read(text: &str) -> Result<nd::Array2<Complex<f64>>> {
+        read_mtx(text.as_bytes())
+    }
+
+    #[test]
+    fn round_trip() {
+        for format in [MtxFormat::Array, MtxFormat::Coordinate] {
+            let mut bytes = Vec::new();
+            write_mtx(&mut bytes, &matrix(), format).unwrap();
+            let text = String::from_utf8(bytes).unwrap();
+            assert!(
+                text.starts_with(&format!("%%MatrixMarket matrix {}", format.name()))
+            );
+            assert_eq!(read(&text).unwrap(), matrix());
+        }
+    }
+
+    #[test]
+    fn round_trip_single_precision() {
+        let matrix = matrix().mapv(|x| Complex::new(x.re as f32, x.im as f32));
+        let mut bytes = Vec::new();
+        write_mtx(&mut bytes, &matrix, MtxFormat::Array).unwrap();
+        assert_eq!(read_mtx::<f32, _>(&bytes[..]).unwrap(), matrix);
+    }
+
+    #[test]
+    fn symmetric_matrices_are_mirrored() {
+        let text = "%%MatrixMarket matrix coordinate real hermitian\n\
+                    % comment\n\
+                    2 2 2\n\
+                    1 1 1.0\n\
+                    2 1 2.0\n";
+        let expected = nd::array![
+            [Complex::new(1.0, 0.0), Complex::new(2.0, 0.0)],
+            [Complex::new(2.0, 0.0), Complex::zero()]
+        ];
+        assert_eq!(read(text).unwrap(), expected);
+
+        let text = "%%MatrixMarket matrix array integer skew-symmetric\n2 2\n3\n";
+        let expected = nd::array![
+            [Complex::zero(), Complex::new(-3.0, 0.0)],
+            [Complex::new(3.0, 0.0), Complex::zero()]
+        ];
+        assert_eq!(read(text).unwrap(), expected);
+    }
+
+    #[test]
+    fn malformed_input_is_rejected() {
+        let cases = [
+            "",
+            "%%MatrixMarket vector array complex general\n1 1\n1 0\n",
+            "%%MatrixMarket matrix dense complex general\n1 1\n1 0\n",
+            "%%MatrixMarket matrix array complex general\n",
+            "%%MatrixMarket matrix array complex general\n1 2\n1 0\n1 0\n",
+            "%%MatrixMarket matrix array complex general\n1\n1 0\n",
+            "%%MatrixMarket matrix array complex general\n1 1\n1\n",
+            "%%MatrixMarket matrix array complex general\n1 1\n1 0\n1 0\n",
+            "%%MatrixMarket matrix array complex general\n2 2\n1 0\n",
+            "%%MatrixMarket matrix coordinate real general\n2 2 1\n3 1 1.0\n",
+            "%%MatrixMarket matrix coordinate real general\n2 2 1\nx 1 1.0\n",
+            "%%MatrixMarket matrix coordinate real symmetric\n2 2 1\n1 2 1.0\n",
+            "%%MatrixMarket matrix coordinate integer general\n2 2 1\n1 1 0.5\n",
+            "%%MatrixMarket matrix coordinate real general\n99999999999 99999999999 0\n",
+            "%%MatrixMarket matrix array real general\n4294967296 4294967296\n",
+        ];
+        for text in cases {
+            assert!(read(text).is_err(), "Accepted malformed input {:?}.", text);
+        }
+    }
+
+    #[test]
+    fn writing_non_square_matrix_is_rejected() {
+        let matrix = nd::Array2::<Complex<f64>>::zeros((2, 3));
+        assert!(write_mtx(Vec::new(), &matrix, MtxFormat::Array).is_err());
+    }
+}
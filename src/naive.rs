@@ -21,18 +21,82 @@
 use std::any;
 use std::f64::consts::PI;
 use std::fmt;
-use std::ops::Sub;
+use std::ops::{ControlFlow, Sub};
+use std::sync::{mpsc, RwLock};
+use std::thread;
 
 use ndarray as nd;
 use num::Complex;
 use num_traits::{Float, Zero};
-use rand::Rng;
+use rand::{Rng, SeedableRng};
 
-use crate::shared::AlgoMode;
+use crate::backend::{Backend, Progress};
+use crate::error::{Error, Result};
+use crate::shared::{AlgoMode, SymmetryMode};
 
-pub fn product<T>(lhs: &nd::Array2<Complex<T>>, rhs: &nd::Array2<Complex<T>>) -> T
+mod checkpoint;
+
+/// Random number generator used by backend, streams of numbers it produces are stable
+/// for given seed.
+pub type BackendRng = rand_chacha::ChaCha8Rng;
+
+/// Create random number generator seeded with `seed`, or with system entropy when
+/// no seed is given.
+pub fn rng_from_seed(seed: Option<u64>) -> BackendRng {
+    match seed {
+        Some(seed) => BackendRng::seed_from_u64(seed),
+        None => BackendRng::from_entropy(),
+    }
+}
+
+/// Ensure `matrix` is square, `name` is used to refer to it in error message.
+pub fn ensure_square<T>(matrix: &nd::ArrayView2<T>, name: &str) -> Result<usize> {
+    let (rows, columns) = matrix.dim();
+    if rows != columns {
+        return Err(Error::Value(format!(
+            "{} has to be square matrix, got shape {:?}.",
+            name,
+            matrix.dim()
+        )));
+    }
+    Ok(rows)
+}
+
+/// Ensure `matrix` is square and has the same shape as `other`, `name` is used to
+/// refer to `matrix` in error message.
+pub fn ensure_same_shape<T>(
+    matrix: &nd::ArrayView2<T>,
+    other: &nd::ArrayView2<T>,
+    name: &str,
+) -> Result<usize> {
+    let size = ensure_square(matrix, name)?;
+    if matrix.dim() != other.dim() {
+        return Err(Error::Value(format!(
+            "{} of shape {:?} does not match shape {:?}.",
+            name,
+            matrix.dim(),
+            other.dim()
+        )));
+    }
+    Ok(size)
+}
+
+/// Size of system composed of subsystems of given `dimensions`, `None` when it
+/// overflows `usize`.
+pub fn system_size(dimensions: &[usize]) -> Option<usize> {
+    dimensions
+        .iter()
+        .try_fold(1_usize, |size, dimension| size.checked_mul(*dimension))
+}
+
+pub fn product<T, S1, S2>(
+    lhs: &nd::ArrayBase<S1, nd::Ix2>,
+    rhs: &nd::ArrayBase<S2, nd::Ix2>,
+) -> T
 where
     T: Float + std::fmt::Debug + rand_distr::uniform::SampleUniform + 'static,
+    S1: nd::Data<Elem = Complex<T>>,
+    S2: nd::Data<Elem = Complex<T>>,
 {
     let square_matrix_size = lhs.dim().0;
     let mut result = T::zero();
@@ -45,32 +109,35 @@ where
     result
 }
 
-pub fn normalize<T>(vec: &nd::Array1<Complex<T>>) -> nd::Array1<Complex<T>>
+pub fn normalize<T, S>(vec: &nd::ArrayBase<S, nd::Ix1>) -> nd::Array1<Complex<T>>
 where
     T: Float + std::fmt::Debug + rand_distr::uniform::SampleUniform + 'static,
+    S: nd::Data<Elem = Complex<T>>,
 {
     let divisor = vec.dot(&vec.mapv(|x| x.conj())).re.sqrt();
     nd::Zip::from(vec).map_collect(|x| x / divisor)
 }
 
-pub fn project<T>(a: &nd::Array1<Complex<T>>) -> nd::Array2<Complex<T>>
+pub fn project<T, S>(a: &nd::ArrayBase<S, nd::Ix1>) -> nd::Array2<Complex<T>>
 where
     T: Float + std::fmt::Debug + rand_distr::uniform::SampleUniform + 'static,
+    S: nd::Data<Elem = Complex<T>>,
 {
-    let length = a.len();
-    let b = a.mapv(|x| x.conj()).into_shape((length, 1)).unwrap();
+    let b = a.mapv(|x| x.conj()).insert_axis(nd::Axis(1));
     // .zip(mtx1_conj.outer_iter()).map_collect(|(x, y)| x * y)
-    let a1 = a.clone().into_shape((1, length)).unwrap();
+    let a1 = a.view().insert_axis(nd::Axis(0));
 
     b.dot(&a1).reversed_axes()
 }
 
-pub fn kronecker<T>(
-    a: &nd::Array2<Complex<T>>,
-    b: &nd::Array2<Complex<T>>,
+pub fn kronecker<T, S1, S2>(
+    a: &nd::ArrayBase<S1, nd::Ix2>,
+    b: &nd::ArrayBase<S2, nd::Ix2>,
 ) -> nd::Array2<Complex<T>>
 where
     T: Float + std::fmt::Debug + rand_distr::uniform::SampleUniform + 'static,
+    S1: nd::Data<Elem = Complex<T>>,
+    S2: nd::Data<Elem = Complex<T>>,
 {
     let ddd1 = a.dim().0;
     let ddd2 = b.dim().0;
@@ -88,68 +155,596 @@ where
     out_mtx.into_shape(output_shape).unwrap()
 }
 
-pub fn rotate<T>(
-    rho2: &nd::Array2<Complex<T>>,
-    unitary: &nd::Array2<Complex<T>>,
+/// Same as `project`, writing projector into `out`.
+pub fn project_into<T, S>(
+    a: &nd::ArrayBase<S, nd::Ix1>,
+    out: &mut nd::Array2<Complex<T>>,
+) where
+    T: Float + std::fmt::Debug + rand_distr::uniform::SampleUniform + 'static,
+    S: nd::Data<Elem = Complex<T>>,
+{
+    nd::Zip::indexed(out).for_each(|(i, j), x| *x = a[i] * a[j].conj());
+}
+
+pub fn rotate<T, S1, S2>(
+    rho2: &nd::ArrayBase<S1, nd::Ix2>,
+    unitary: &nd::ArrayBase<S2, nd::Ix2>,
 ) -> nd::Array2<Complex<T>>
 where
     T: Float + std::fmt::Debug + rand_distr::uniform::SampleUniform + 'static,
+    S1: nd::Data<Elem = Complex<T>>,
+    S2: nd::Data<Elem = Complex<T>>,
+{
+    let mut buffer = nd::Array2::zeros(rho2.dim());
+    let mut output = nd::Array2::zeros(rho2.dim());
+    rotate_into(rho2, unitary, &mut buffer, &mut output);
+    output
+}
+
+/// Same as `rotate`, writing result into `out`. `buffer` has to be of the same shape
+/// as `out`, its content is overwritten.
+pub fn rotate_into<T, S1, S2>(
+    rho2: &nd::ArrayBase<S1, nd::Ix2>,
+    unitary: &nd::ArrayBase<S2, nd::Ix2>,
+    buffer: &mut nd::Array2<Complex<T>>,
+    out: &mut nd::Array2<Complex<T>>,
+) where
+    T: Float + std::fmt::Debug + rand_distr::uniform::SampleUniform + 'static,
+    S1: nd::Data<Elem = Complex<T>>,
+    S2: nd::Data<Elem = Complex<T>>,
 {
-    let unitary_conj_transpose = unitary.mapv(|x| x.conj()).reversed_axes();
-    let rho2a = rho2.dot(&unitary_conj_transpose);
-    unitary.dot(&rho2a)
+    let one = Complex::<T>::new(T::one(), T::zero());
+    let zero = Complex::<T>::zero();
+
+    // rho2 U^dagger = conj(conj(rho2) U^T), which does not need U^dagger in memory.
+    nd::Zip::from(&mut *buffer)
+        .and(rho2)
+        .for_each(|x, y| *x = y.conj());
+    nd::linalg::general_mat_mul(one, &*buffer, &unitary.t(), zero, out);
+    out.mapv_inplace(|x| x.conj());
+
+    nd::linalg::general_mat_mul(one, unitary, &*out, zero, buffer);
+    std::mem::swap(buffer, out);
 }
 
-pub fn get_random_haar_1d<T>(depth: usize) -> nd::Array1<Complex<T>>
+/// Replace square `matrix` with its conjugate transpose.
+fn adjoint_inplace<T>(matrix: &mut nd::Array2<Complex<T>>)
 where
     T: Float + std::fmt::Debug + rand_distr::uniform::SampleUniform + 'static,
 {
-    let normal =
-        rand_distr::Uniform::<T>::new(T::from(0.0).unwrap(), T::from(1.0).unwrap());
+    let size = matrix.dim().0;
+    for i in 0..size {
+        for j in i + 1..size {
+            matrix.swap([i, j], [j, i]);
+        }
+    }
+    matrix.mapv_inplace(|x| x.conj());
+}
 
-    let mut rng_real = rand::thread_rng();
+pub fn get_random_haar_1d<T, R>(depth: usize, rng: &mut R) -> nd::Array1<Complex<T>>
+where
+    T: Float + std::fmt::Debug + rand_distr::uniform::SampleUniform + 'static,
+    R: Rng + ?Sized,
+{
+    let mut vector = nd::Array1::zeros(depth);
+    get_random_haar_1d_into(&mut vector, rng);
+    vector
+}
 
-    let real = (&mut rng_real)
-        .sample_iter(&normal)
-        .take(depth)
-        .collect::<Vec<T>>();
+/// Same as `get_random_haar_1d`, filling `out` with vector of its length.
+pub fn get_random_haar_1d_into<T, R, S>(
+    out: &mut nd::ArrayBase<S, nd::Ix1>,
+    rng: &mut R,
+) where
+    T: Float + std::fmt::Debug + rand_distr::uniform::SampleUniform + 'static,
+    R: Rng + ?Sized,
+    S: nd::DataMut<Elem = Complex<T>>,
+{
+    let normal =
+        rand_distr::Uniform::<T>::new(T::from(0.0).unwrap(), T::from(1.0).unwrap());
 
-    let imaginary = (&mut rng_real)
-        .sample_iter(&normal)
-        .take(depth)
-        .collect::<Vec<T>>();
+    // All real samples are drawn before imaginary ones, real parts of output hold
+    // them in the meantime.
+    for x in out.iter_mut() {
+        *x = Complex::<T>::new(rng.sample(&normal), T::zero());
+    }
 
-    nd::Zip::from(&real).and(&imaginary).map_collect(|r, i| {
-        let in_exp = T::from(2.0f64).unwrap() * T::from(PI).unwrap() * *r;
+    for x in out.iter_mut() {
+        let i: T = rng.sample(&normal);
+        let in_exp = T::from(2.0f64).unwrap() * T::from(PI).unwrap() * x.re;
         let c_r = Complex::<T>::new(T::zero(), in_exp);
-        let c_i = (-T::ln(*i)).sqrt();
+        let c_i = (-T::ln(i)).sqrt();
 
-        Complex::<T>::exp(c_r) * c_i
-    })
+        *x = Complex::<T>::exp(c_r) * c_i;
+    }
 }
 
-fn apply_symmetries<T>(
-    state: &nd::Array2<Complex<T>>,
-    symmetries: &Vec<Vec<nd::Array2<Complex<T>>>>,
-) -> nd::Array2<Complex<T>>
+/// Normalize vector in place.
+fn normalize_inplace<T, S>(vec: &mut nd::ArrayBase<S, nd::Ix1>)
 where
     T: Float + std::fmt::Debug + rand_distr::uniform::SampleUniform + 'static,
+    S: nd::DataMut<Elem = Complex<T>>,
 {
-    let mut output = state.to_owned();
+    let divisor = vec
+        .iter()
+        .fold(T::zero(), |acc, x| acc + (x * x.conj()).re)
+        .sqrt();
+    vec.mapv_inplace(|x| x / divisor);
+}
 
+/// Apply rows of symmetries to `state` in place, `buffer` and `rotated` are scratch
+/// matrices of the same shape as `state`.
+fn apply_symmetries_into<T>(
+    state: &mut nd::Array2<Complex<T>>,
+    symmetries: &Vec<Vec<nd::Array2<Complex<T>>>>,
+    buffer: &mut nd::Array2<Complex<T>>,
+    rotated: &mut nd::Array2<Complex<T>>,
+) where
+    T: Float + std::fmt::Debug + rand_distr::uniform::SampleUniform + 'static,
+{
     for row in symmetries {
         for symmetry in row {
-            output = rotate(&output, symmetry) + output;
+            rotate_into(&*state, symmetry, buffer, rotated);
+            state.zip_mut_with(&*rotated, |x, y| *x = *x + *y);
         }
     }
 
     let mut trace = Complex::<T>::zero();
-    for i in 0..output.dim().0 {
-        trace = trace + output[[i, i]];
+    for i in 0..state.dim().0 {
+        trace = trace + state[[i, i]];
     }
 
-    output = output.map(|x| *x / trace);
-    output
+    state.mapv_inplace(|x| x / trace);
+}
+
+/// Upper bound on size of group generated from symmetries, guards against generators
+/// of infinite groups.
+const MAX_SYMMETRY_GROUP_SIZE: usize = 4096;
+
+/// Symmetries of the state, either rows of matrices applied one after another or all
+/// elements of finite group of unitaries.
+#[derive(Clone)]
+enum Symmetries<T> {
+    Sequential(Vec<Vec<nd::Array2<Complex<T>>>>),
+    Group(Vec<nd::Array2<Complex<T>>>),
+}
+
+/// Average state uniformly over all elements of symmetry group in place, `buffer`,
+/// `rotated` and `accumulated` are scratch matrices of the same shape as `state`.
+fn apply_symmetry_group_into<T>(
+    state: &mut nd::Array2<Complex<T>>,
+    group: &[nd::Array2<Complex<T>>],
+    buffer: &mut nd::Array2<Complex<T>>,
+    rotated: &mut nd::Array2<Complex<T>>,
+    accumulated: &mut nd::Array2<Complex<T>>,
+) where
+    T: Float + std::fmt::Debug + rand_distr::uniform::SampleUniform + 'static,
+{
+    accumulated.fill(Complex::<T>::zero());
+
+    for element in group {
+        rotate_into(&*state, element, buffer, rotated);
+        accumulated.zip_mut_with(&*rotated, |x, y| *x = *x + *y);
+    }
+
+    let group_size = T::from(group.len()).unwrap();
+    accumulated.mapv_inplace(|x| x / group_size);
+    std::mem::swap(state, accumulated);
+}
+
+/// Check that matrix averaged over `group` with `apply_symmetry_group_into` is invariant
+/// under every element of the group, probed with single matrix without symmetries.
+fn is_averaging_invariant<T>(group: &[nd::Array2<Complex<T>>], size: usize) -> bool
+where
+    T: Float + std::fmt::Debug + rand_distr::uniform::SampleUniform + 'static,
+{
+    let scale = T::from(size * size).unwrap();
+    let mut state = nd::Array2::from_shape_fn((size, size), |(i, j)| {
+        Complex::new(T::from(i + 1).unwrap(), T::from(j * j).unwrap()) / scale
+    });
+    let mut buffer = nd::Array2::zeros((size, size));
+    let mut rotated = nd::Array2::zeros((size, size));
+    let mut accumulated = nd::Array2::zeros((size, size));
+    apply_symmetry_group_into(
+        &mut state,
+        group,
+        &mut buffer,
+        &mut rotated,
+        &mut accumulated,
+    );
+
+    group
+        .iter()
+        .all(|element| is_close(&rotate(&state, element), &state))
+}
+
+/// Absolute tolerance used when comparing matrices element-wise.
+fn tolerance<T: Float>() -> T {
+    T::epsilon().sqrt()
+}
+
+fn is_close<T>(lhs: &nd::Array2<Complex<T>>, rhs: &nd::Array2<Complex<T>>) -> bool
+where
+    T: Float + std::fmt::Debug + rand_distr::uniform::SampleUniform + 'static,
+{
+    let tolerance = tolerance::<T>();
+    nd::Zip::from(lhs)
+        .and(rhs)
+        .all(|x, y| (*x - *y).norm() <= tolerance)
+}
+
+fn is_unitary<T>(matrix: &nd::Array2<Complex<T>>) -> bool
+where
+    T: Float + std::fmt::Debug + rand_distr::uniform::SampleUniform + 'static,
+{
+    let matrix_conj_transpose = matrix.mapv(|x| x.conj()).reversed_axes();
+    is_close(
+        &matrix.dot(&matrix_conj_transpose),
+        &nd::Array2::eye(matrix.dim().0),
+    )
+}
+
+/// Close set of unitaries under multiplication, yielding all elements of the group
+/// they generate, starting with identity.
+fn generate_group<T>(
+    generators: &[nd::Array2<Complex<T>>],
+    size: usize,
+) -> Result<Vec<nd::Array2<Complex<T>>>>
+where
+    T: Float + std::fmt::Debug + rand_distr::uniform::SampleUniform + 'static,
+{
+    let mut group = vec![nd::Array2::<Complex<T>>::eye(size)];
+    let mut index = 0;
+
+    while index < group.len() {
+        for generator in generators {
+            let element = generator.dot(&group[index]);
+
+            if !group.iter().any(|known| is_close(known, &element)) {
+                if group.len() == MAX_SYMMETRY_GROUP_SIZE {
+                    return Err(Error::Value(format!(
+                        "Symmetries generate group larger than {} elements.",
+                        MAX_SYMMETRY_GROUP_SIZE
+                    )));
+                }
+                group.push(element);
+            }
+        }
+        index += 1;
+    }
+    Ok(group)
+}
+
+/// Check if finite set of unitaries is closed under multiplication, thus is a group.
+fn is_group<T>(elements: &[nd::Array2<Complex<T>>]) -> bool
+where
+    T: Float + std::fmt::Debug + rand_distr::uniform::SampleUniform + 'static,
+{
+    elements.iter().all(|lhs| {
+        elements.iter().all(|rhs| {
+            let element = lhs.dot(rhs);
+            elements.iter().any(|known| is_close(known, &element))
+        })
+    })
+}
+
+/// Pure product state `|f_0> ⊗ |f_1> ⊗ ...` kept as its normalized local factors,
+/// so that it can be rotated and measured without building its `D×D` projector.
+/// Factor may span more than one subsystem, factors are laid out over subsystems in
+/// `order`, ie. subsystem `k` of tensor product of factors is subsystem `order[k]`
+/// of the system.
+#[derive(Debug)]
+pub struct ProductState<T> {
+    factors: Vec<nd::Array1<Complex<T>>>,
+    dimensions: Vec<usize>,
+    order: Vec<usize>,
+    // Dimensions of subsystems in `order` and order restoring original subsystems,
+    // kept to avoid computing them for every state vector.
+    layout: Vec<usize>,
+    inverse: Vec<usize>,
+}
+
+impl<T: Clone> Clone for ProductState<T> {
+    fn clone(&self) -> Self {
+        Self {
+            factors: self.factors.clone(),
+            dimensions: self.dimensions.clone(),
+            order: self.order.clone(),
+            layout: self.layout.clone(),
+            inverse: self.inverse.clone(),
+        }
+    }
+
+    // Reuses buffers of `self`, so that screened candidates are copied without
+    // allocating once buffers are large enough.
+    fn clone_from(&mut self, source: &Self) {
+        self.factors.clone_from(&source.factors);
+        self.dimensions.clone_from(&source.dimensions);
+        self.order.clone_from(&source.order);
+        self.layout.clone_from(&source.layout);
+        self.inverse.clone_from(&source.inverse);
+    }
+}
+
+impl<T> ProductState<T>
+where
+    T: Float + std::fmt::Debug + rand_distr::uniform::SampleUniform + 'static,
+{
+    fn with_factors(dimensions: &[usize], order: Vec<usize>, depths: &[usize]) -> Self {
+        Self {
+            factors: depths
+                .iter()
+                .map(|depth| nd::Array1::zeros(*depth))
+                .collect(),
+            dimensions: dimensions.to_vec(),
+            layout: permute_dimensions(dimensions, &order),
+            inverse: inverse_order(&order),
+            order,
+        }
+    }
+
+    /// Product state with one factor per subsystem, all factors are zero until filled
+    /// with `fill_random`.
+    pub fn zeros(dimensions: &[usize]) -> Self {
+        Self::with_factors(dimensions, (0..dimensions.len()).collect(), dimensions)
+    }
+
+    /// Pure state separable with respect to bipartition of subsystems into `block` and
+    /// its complement, each of the blocks is a single factor. All factors are zero until
+    /// filled with `fill_random`.
+    pub fn zeros_cut(dimensions: &[usize], block: &[usize]) -> Self {
+        let (depth_1, depth_2) = bipartition_dimensions(dimensions, block);
+        Self::with_factors(
+            dimensions,
+            bipartition_order(block, dimensions.len()),
+            &[depth_1, depth_2],
+        )
+    }
+
+    /// Random product state with one factor per subsystem.
+    pub fn random<R>(dimensions: &[usize], rng: &mut R) -> Self
+    where
+        R: Rng + ?Sized,
+    {
+        let mut state = Self::zeros(dimensions);
+        state.fill_random(rng);
+        state
+    }
+
+    /// Replace factors with random normalized vectors.
+    pub fn fill_random<R>(&mut self, rng: &mut R)
+    where
+        R: Rng + ?Sized,
+    {
+        for factor in self.factors.iter_mut() {
+            get_random_haar_1d_into(factor, rng);
+            normalize_inplace(factor);
+        }
+    }
+
+    /// Copy factors of `other` state of the same shape.
+    pub fn assign(&mut self, other: &Self) {
+        for (factor, other) in self.factors.iter_mut().zip(other.factors.iter()) {
+            factor.assign(other);
+        }
+    }
+
+    /// Check whether `other` has the same factors laid out over the same subsystems.
+    pub fn has_shape_of(&self, other: &Self) -> bool {
+        self.order == other.order
+            && self
+                .factors
+                .iter()
+                .zip(other.factors.iter())
+                .all(|(factor, other)| factor.len() == other.len())
+    }
+
+    /// Dimensions of local factors.
+    pub fn factor_dimensions(&self) -> Vec<usize> {
+        self.factors.iter().map(|factor| factor.len()).collect()
+    }
+
+    /// State vector in the basis of the system.
+    pub fn vector(&self) -> nd::Array1<Complex<T>> {
+        let size = system_size(&self.dimensions).expect("Product state is too large.");
+        let mut vector = nd::Array1::zeros(size);
+        self.vector_into(&mut vector, &mut nd::Array1::zeros(size));
+        vector
+    }
+
+    /// Same as `vector`, writing state vector into `out`. `buffer` has to be of the same
+    /// length as `out`, its content is overwritten.
+    pub fn vector_into(
+        &self,
+        out: &mut nd::Array1<Complex<T>>,
+        buffer: &mut nd::Array1<Complex<T>>,
+    ) {
+        let is_permuted = self
+            .order
+            .iter()
+            .enumerate()
+            .any(|(idx, other)| idx != *other);
+        let target = if is_permuted { &mut *buffer } else { &mut *out };
+        // Some subsystem is zero dimensional, so is the state vector.
+        if target.is_empty() {
+            return;
+        }
+
+        // Tensor product is built in place, from the back, so that every entry is read
+        // before it is overwritten.
+        target[0] = Complex::<T>::new(T::one(), T::zero());
+        let mut length = 1;
+        for factor in &self.factors {
+            let depth = factor.len();
+            for i in (0..length).rev() {
+                let lhs = target[i];
+                for j in (0..depth).rev() {
+                    target[i * depth + j] = lhs * factor[j];
+                }
+            }
+            length *= depth;
+        }
+
+        if is_permuted {
+            let tensor = buffer.view().into_shape(nd::IxDyn(&self.layout)).unwrap();
+            out.view_mut()
+                .into_shape(nd::IxDyn(&self.dimensions))
+                .unwrap()
+                .assign(&tensor.permuted_axes(nd::IxDyn(&self.inverse)));
+        }
+    }
+
+    /// Density matrix of the state.
+    pub fn projector(&self) -> nd::Array2<Complex<T>> {
+        project(&self.vector())
+    }
+
+    /// Same as `projector`, writing density matrix into `out`. `vector` and `buffer`
+    /// are scratch vectors of length of the state vector.
+    pub fn projector_into(
+        &self,
+        out: &mut nd::Array2<Complex<T>>,
+        vector: &mut nd::Array1<Complex<T>>,
+        buffer: &mut nd::Array1<Complex<T>>,
+    ) {
+        self.vector_into(vector, buffer);
+        project_into(&*vector, out);
+    }
+
+    /// Same as `product(&self.projector(), operator)`, computed as <psi|X|psi>.
+    /// `vector` and `buffer` are scratch vectors of length of the state vector.
+    pub fn product_into<S>(
+        &self,
+        operator: &nd::ArrayBase<S, nd::Ix2>,
+        vector: &mut nd::Array1<Complex<T>>,
+        buffer: &mut nd::Array1<Complex<T>>,
+    ) -> T
+    where
+        S: nd::Data<Elem = Complex<T>>,
+    {
+        self.vector_into(vector, buffer);
+        operator
+            .outer_iter()
+            .zip(vector.iter())
+            .fold(T::zero(), |acc, (row, lhs)| {
+                acc + (lhs.conj() * row.dot(&*vector)).re
+            })
+    }
+
+    /// Apply `unitary` to factor `idx` in place, `scratch` is a vector of length of
+    /// that factor.
+    pub fn rotate_factor<S>(
+        &mut self,
+        unitary: &nd::ArrayBase<S, nd::Ix2>,
+        idx: usize,
+        scratch: &mut nd::Array1<Complex<T>>,
+    ) where
+        S: nd::Data<Elem = Complex<T>>,
+    {
+        let one = Complex::<T>::new(T::one(), T::zero());
+        nd::linalg::general_mat_vec_mul(
+            one,
+            unitary,
+            &self.factors[idx],
+            Complex::<T>::zero(),
+            scratch,
+        );
+        std::mem::swap(&mut self.factors[idx], scratch);
+    }
+}
+
+/// Buffers reused by `optimize_product_into`, sized for product states of one shape.
+#[derive(Clone, Debug)]
+pub struct ProductWorkspace<T> {
+    optimized: ProductState<T>,
+    unitaries: Vec<nd::Array2<Complex<T>>>,
+    scratch: Vec<nd::Array1<Complex<T>>>,
+    vector: nd::Array1<Complex<T>>,
+    buffer: nd::Array1<Complex<T>>,
+}
+
+impl<T> ProductWorkspace<T>
+where
+    T: Float + std::fmt::Debug + rand_distr::uniform::SampleUniform + 'static,
+{
+    /// Create buffers for product states of the same shape as `state`.
+    pub fn new(state: &ProductState<T>) -> Self {
+        let depths = state.factor_dimensions();
+        let size = depths.iter().product::<usize>();
+        Self {
+            optimized: state.clone(),
+            unitaries: depths
+                .iter()
+                .map(|depth| nd::Array2::zeros((*depth, *depth)))
+                .collect(),
+            scratch: depths
+                .iter()
+                .map(|depth| nd::Array1::zeros(*depth))
+                .collect(),
+            vector: nd::Array1::zeros(size),
+            buffer: nd::Array1::zeros(size),
+        }
+    }
+
+    /// Result of last `optimize_product_into` call.
+    pub fn optimized(&self) -> &ProductState<T> {
+        &self.optimized
+    }
+}
+
+/// Counterpart of `optimize_d_fs` working on local factors of product state,
+/// consecutive updates rotate consecutive factors. Optimized state is left in
+/// `workspace`, see `ProductWorkspace::optimized`.
+pub fn optimize_product_into<T, R, S>(
+    new_state: &ProductState<T>,
+    visibility_state: &nd::ArrayBase<S, nd::Ix2>,
+    updates_count: usize,
+    rng: &mut R,
+    workspace: &mut ProductWorkspace<T>,
+) where
+    T: Float + std::fmt::Debug + rand_distr::uniform::SampleUniform + 'static,
+    R: Rng + ?Sized,
+    S: nd::Data<Elem = Complex<T>>,
+{
+    let ProductWorkspace {
+        optimized: rotated_2,
+        unitaries,
+        scratch,
+        vector,
+        buffer,
+    } = workspace;
+    let quantity = unitaries.len();
+
+    let mut product_2_3 = new_state.product_into(visibility_state, vector, buffer);
+    _random_unitary_d_fs_into(&mut unitaries[0], &mut scratch[0], rng);
+    rotated_2.assign(new_state);
+    rotated_2.rotate_factor(&unitaries[0], 0, &mut scratch[0]);
+
+    for idx in 0..updates_count {
+        let idx_mod = idx % quantity;
+        let unitary = &mut unitaries[idx_mod];
+        let scratch = &mut scratch[idx_mod];
+        _random_unitary_d_fs_into(unitary, scratch, rng);
+
+        rotated_2.assign(new_state);
+        rotated_2.rotate_factor(&*unitary, idx_mod, scratch);
+
+        let mut product_rot2_3 =
+            rotated_2.product_into(visibility_state, vector, buffer);
+
+        if product_2_3 > product_rot2_3 {
+            adjoint_inplace(unitary);
+            rotated_2.assign(new_state);
+            rotated_2.rotate_factor(&*unitary, idx_mod, scratch);
+        }
+
+        while product_2_3 > product_rot2_3 {
+            product_2_3 = product_rot2_3;
+            rotated_2.rotate_factor(&*unitary, idx_mod, scratch);
+
+            product_rot2_3 = rotated_2.product_into(visibility_state, vector, buffer);
+        }
+    }
 }
 
 //   ██████     ███████    ███████            ███    ███     ██████     ██████     ███████
@@ -158,51 +753,177 @@ where
 //   ██   ██    ██              ██            ██  ██  ██    ██    ██    ██   ██    ██
 //   ██████     ██         ███████            ██      ██     ██████     ██████     ███████
 
-pub fn expand_d_fs<T>(
-    value: &nd::Array2<Complex<T>>,
-    depth: usize,
-    quantity: usize,
+pub fn expand_d_fs<T, S>(
+    value: &nd::ArrayBase<S, nd::Ix2>,
+    dimensions: &[usize],
+    idx: usize,
+) -> nd::Array2<Complex<T>>
+where
+    T: Float + std::fmt::Debug + rand_distr::uniform::SampleUniform + 'static,
+    S: nd::Data<Elem = Complex<T>>,
+{
+    let size = system_size(&dimensions[..idx])
+        .zip(system_size(&dimensions[idx + 1..]))
+        .and_then(|(depth_1, depth_2)| {
+            depth_1.checked_mul(value.dim().0)?.checked_mul(depth_2)
+        })
+        .expect("Expanded system is too large.");
+
+    let mut output = nd::Array2::zeros((size, size));
+    expand_d_fs_into(value, dimensions, idx, &mut output);
+    output
+}
+
+/// Same as `expand_d_fs`, writing `I ⊗ value ⊗ I` into `out`.
+pub fn expand_d_fs_into<T, S>(
+    value: &nd::ArrayBase<S, nd::Ix2>,
+    dimensions: &[usize],
+    idx: usize,
+    out: &mut nd::Array2<Complex<T>>,
+) where
+    T: Float + std::fmt::Debug + rand_distr::uniform::SampleUniform + 'static,
+    S: nd::Data<Elem = Complex<T>>,
+{
+    let depth = value.dim().0;
+    let depth_1 = dimensions[..idx].iter().product::<usize>();
+    let depth_2 = dimensions[idx + 1..].iter().product::<usize>();
+
+    out.fill(Complex::<T>::zero());
+    for outer in 0..depth_1 {
+        for inner in 0..depth_2 {
+            for ((i, j), x) in value.indexed_iter() {
+                out[[
+                    (outer * depth + i) * depth_2 + inner,
+                    (outer * depth + j) * depth_2 + inner,
+                ]] = *x;
+            }
+        }
+    }
+}
+
+/// Apply `unitary` to subsystem `idx` of `rho`, equivalent to
+/// `rotate(rho, &expand_d_fs(unitary, dimensions, idx))`, but contracting only the
+/// rotated axis instead of building the full-size operator.
+pub fn rotate_d_fs<T, S1, S2>(
+    rho: &nd::ArrayBase<S1, nd::Ix2>,
+    unitary: &nd::ArrayBase<S2, nd::Ix2>,
+    dimensions: &[usize],
     idx: usize,
 ) -> nd::Array2<Complex<T>>
 where
     T: Float + std::fmt::Debug + rand_distr::uniform::SampleUniform + 'static,
+    S1: nd::Data<Elem = Complex<T>>,
+    S2: nd::Data<Elem = Complex<T>>,
 {
-    let depth_1 = depth.pow(idx as u32);
-    let identity_1 = nd::Array::eye(depth_1);
+    let mut buffer = nd::Array2::zeros(rho.dim());
+    let mut output = nd::Array2::zeros(rho.dim());
+    rotate_d_fs_into(rho, unitary, dimensions, idx, &mut buffer, &mut output);
+    output
+}
 
-    let depth_2 = depth.pow((quantity - idx - 1) as u32);
-    let identity_2 = nd::Array::eye(depth_2);
+/// Same as `rotate_d_fs`, writing result into `out`. `buffer` has to be of the same
+/// shape as `out`, its content is overwritten. Both have to be in standard layout,
+/// `rho` in other layout is copied.
+pub fn rotate_d_fs_into<T, S1, S2>(
+    rho: &nd::ArrayBase<S1, nd::Ix2>,
+    unitary: &nd::ArrayBase<S2, nd::Ix2>,
+    dimensions: &[usize],
+    idx: usize,
+    buffer: &mut nd::Array2<Complex<T>>,
+    out: &mut nd::Array2<Complex<T>>,
+) where
+    T: Float + std::fmt::Debug + rand_distr::uniform::SampleUniform + 'static,
+    S1: nd::Data<Elem = Complex<T>>,
+    S2: nd::Data<Elem = Complex<T>>,
+{
+    let size = rho.dim().0;
+    let depth = dimensions[idx];
+    let depth_1: usize = dimensions[..idx].iter().product();
+    let depth_2: usize = dimensions[idx + 1..].iter().product();
+
+    let one = Complex::<T>::new(T::one(), T::zero());
+    let zero = Complex::<T>::zero();
+
+    // Row index of rho splits into (depth_1, depth, depth_2), multiply U from the
+    // left into the middle axis.
+    let rho = rho.as_standard_layout();
+    let rows = rho
+        .view()
+        .into_shape((depth_1, depth, depth_2 * size))
+        .unwrap();
+    let mut rotated = buffer
+        .view_mut()
+        .into_shape((depth_1, depth, depth_2 * size))
+        .unwrap();
+    for (block, mut target) in rows.outer_iter().zip(rotated.outer_iter_mut()) {
+        nd::linalg::general_mat_mul(one, unitary, &block, zero, &mut target);
+    }
 
-    let kronecker_1 = kronecker(&identity_1, value);
-    kronecker(&kronecker_1, &identity_2)
+    // Column index splits the same way, multiplying by U^dagger from the right is
+    // the same as multiplying by conj(U) from the left along that axis, which in turn
+    // is conj(U conj(X)).
+    buffer.mapv_inplace(|x| x.conj());
+    let columns = buffer
+        .view()
+        .into_shape((size * depth_1, depth, depth_2))
+        .unwrap();
+    let mut output = out
+        .view_mut()
+        .into_shape((size * depth_1, depth, depth_2))
+        .unwrap();
+    for (block, mut target) in columns.outer_iter().zip(output.outer_iter_mut()) {
+        nd::linalg::general_mat_mul(one, unitary, &block, zero, &mut target);
+    }
+    out.mapv_inplace(|x| x.conj());
 }
 
-pub fn random_unitary_d_fs<T>(
-    depth: usize,
-    quantity: usize,
+pub fn random_unitary_d_fs<T, R>(
+    dimensions: &[usize],
     idx: usize,
+    rng: &mut R,
 ) -> nd::Array2<Complex<T>>
 where
     T: Float + std::fmt::Debug + rand_distr::uniform::SampleUniform + 'static,
+    R: Rng + ?Sized,
 {
-    let value = _random_unitary_d_fs::<T>(depth);
-    expand_d_fs(&value, depth, quantity, idx)
+    let value = _random_unitary_d_fs::<T, R>(dimensions[idx], rng);
+    expand_d_fs(&value, dimensions, idx)
 }
 
-pub fn _random_unitary_d_fs<T>(depth: usize) -> nd::Array2<Complex<T>>
+fn _random_unitary_d_fs<T, R>(depth: usize, rng: &mut R) -> nd::Array2<Complex<T>>
 where
     T: Float + std::fmt::Debug + rand_distr::uniform::SampleUniform + 'static,
+    R: Rng + ?Sized,
+{
+    let mut unitary = nd::Array2::zeros((depth, depth));
+    _random_unitary_d_fs_into(&mut unitary, &mut nd::Array1::zeros(depth), rng);
+    unitary
+}
+
+/// Same as `_random_unitary_d_fs`, writing unitary into `out`. `vector` is a scratch
+/// vector of length of side of `out`.
+fn _random_unitary_d_fs_into<T, R>(
+    out: &mut nd::Array2<Complex<T>>,
+    vector: &mut nd::Array1<Complex<T>>,
+    rng: &mut R,
+) where
+    T: Float + std::fmt::Debug + rand_distr::uniform::SampleUniform + 'static,
+    R: Rng + ?Sized,
 {
-    let random_mtx = get_random_haar_1d(depth).into_shape((depth, 1)).unwrap();
-    let identity_mtx = nd::Array2::<Complex<T>>::eye(depth);
+    // I + (e^(i*phi) - 1) |v><v| only rotates the phase of the |v> component, so
+    // it stays unitary as long as |v> is normalized.
+    get_random_haar_1d_into(vector, rng);
+    normalize_inplace(vector);
 
     let value = _value::<T>();
-    let rand_mul = random_mtx.mapv(|x| value * x);
-
-    rand_mul + identity_mtx
+    let one = Complex::<T>::new(T::one(), T::zero());
+    nd::Zip::indexed(out).for_each(|(i, j), x| {
+        let identity = if i == j { one } else { Complex::<T>::zero() };
+        *x = value * (vector[i] * vector[j].conj()) + identity;
+    });
 }
 
-pub fn _value<T>() -> Complex<T>
+fn _value<T>() -> Complex<T>
 where
     T: Float + std::fmt::Debug + rand_distr::uniform::SampleUniform + 'static,
 {
@@ -212,62 +933,47 @@ where
     Complex::new(real.cos() - T::one(), imaginary.sin())
 }
 
-pub fn random_d_fs<T>(depth: usize, quantity: usize) -> nd::Array2<Complex<T>>
+pub fn random_d_fs<T, R>(dimensions: &[usize], rng: &mut R) -> nd::Array2<Complex<T>>
 where
     T: Float + std::fmt::Debug + rand_distr::uniform::SampleUniform + 'static,
+    R: Rng + ?Sized,
 {
-    let vector = normalize(&get_random_haar_1d(depth));
-    let mut vector_2d;
-
-    vector_2d = vector.into_shape((depth, 1)).unwrap();
-
-    let mut vector_width = depth;
-
-    for _ in 1..quantity {
-        let rand_vector = get_random_haar_1d(depth);
-        let normalized_rand_vector = normalize(&rand_vector);
-
-        let normalized_rand_vector_2d =
-            normalized_rand_vector.into_shape((1, depth)).unwrap();
-
-        let matrix = vector_2d.dot(&normalized_rand_vector_2d);
-        vector_width *= depth;
-
-        vector_2d = matrix.into_shape((vector_width, 1)).unwrap();
-    }
-    project(&vector_2d.into_shape((vector_width,)).unwrap())
+    ProductState::random(dimensions, rng).projector()
 }
 
-pub fn optimize_d_fs<T>(
-    new_state: &nd::Array2<Complex<T>>,
-    visibility_state: &nd::Array2<Complex<T>>,
-    depth: usize,
-    quantity: usize,
+pub fn optimize_d_fs<T, R, S1, S2>(
+    new_state: &nd::ArrayBase<S1, nd::Ix2>,
+    visibility_state: &nd::ArrayBase<S2, nd::Ix2>,
+    dimensions: &[usize],
     updates_count: usize,
+    rng: &mut R,
 ) -> nd::Array2<Complex<T>>
 where
     T: Float + std::fmt::Debug + rand_distr::uniform::SampleUniform + 'static,
+    R: Rng + ?Sized,
+    S1: nd::Data<Elem = Complex<T>>,
+    S2: nd::Data<Elem = Complex<T>>,
 {
     let mut product_2_3 = product(new_state, visibility_state);
-    let mut unitary = random_unitary_d_fs(depth, quantity, 0);
-    let mut rotated_2 = rotate(new_state, &unitary);
+    let mut unitary = _random_unitary_d_fs(dimensions[0], rng);
+    let mut rotated_2 = rotate_d_fs(new_state, &unitary, dimensions, 0);
 
     for idx in 0..updates_count {
-        let idx_mod = idx % quantity;
-        unitary = random_unitary_d_fs(depth, quantity, idx_mod);
+        let idx_mod = idx % dimensions.len();
+        unitary = _random_unitary_d_fs(dimensions[idx_mod], rng);
 
-        rotated_2 = rotate(new_state, &unitary);
+        rotated_2 = rotate_d_fs(new_state, &unitary, dimensions, idx_mod);
 
         let mut product_rot2_3 = product(&rotated_2, visibility_state);
 
         if product_2_3 > product_rot2_3 {
             unitary = unitary.mapv(|x| x.conj()).t().to_owned();
-            rotated_2 = rotate(new_state, &unitary);
+            rotated_2 = rotate_d_fs(new_state, &unitary, dimensions, idx_mod);
         }
 
         while product_2_3 > product_rot2_3 {
             product_2_3 = product_rot2_3;
-            rotated_2 = rotate(&rotated_2, &unitary);
+            rotated_2 = rotate_d_fs(&rotated_2, &unitary, dimensions, idx_mod);
 
             product_rot2_3 = product(&rotated_2, visibility_state);
         }
@@ -276,6 +982,457 @@ where
     rotated_2
 }
 
+//   ██████     ███████            ███    ███     ██████     ██████     ███████
+//   ██   ██    ██                 ████  ████    ██    ██    ██   ██    ██
+//   ██████     ███████            ██ ████ ██    ██    ██    ██   ██    █████
+//   ██   ██         ██            ██  ██  ██    ██    ██    ██   ██    ██
+//   ██████     ███████            ██      ██     ██████     ██████     ███████
+
+pub fn random_bs<T, R>(
+    depth: usize,
+    quantity: usize,
+    rng: &mut R,
+) -> nd::Array2<Complex<T>>
+where
+    T: Float + std::fmt::Debug + rand_distr::uniform::SampleUniform + 'static,
+    R: Rng + ?Sized,
+{
+    random_d_fs(&[depth, quantity], rng)
+}
+
+pub fn random_unitary_bs<T, R>(
+    depth: usize,
+    quantity: usize,
+    rng: &mut R,
+) -> nd::Array2<Complex<T>>
+where
+    T: Float + std::fmt::Debug + rand_distr::uniform::SampleUniform + 'static,
+    R: Rng + ?Sized,
+{
+    random_unitary_d_fs(&[depth, quantity], 0, rng)
+}
+
+pub fn random_unitary_bs_reverse<T, R>(
+    depth: usize,
+    quantity: usize,
+    rng: &mut R,
+) -> nd::Array2<Complex<T>>
+where
+    T: Float + std::fmt::Debug + rand_distr::uniform::SampleUniform + 'static,
+    R: Rng + ?Sized,
+{
+    random_unitary_d_fs(&[depth, quantity], 1, rng)
+}
+
+pub fn optimize_bs<T, R, S1, S2>(
+    new_state: &nd::ArrayBase<S1, nd::Ix2>,
+    visibility_state: &nd::ArrayBase<S2, nd::Ix2>,
+    depth: usize,
+    quantity: usize,
+    updates_count: usize,
+    rng: &mut R,
+) -> nd::Array2<Complex<T>>
+where
+    T: Float + std::fmt::Debug + rand_distr::uniform::SampleUniform + 'static,
+    R: Rng + ?Sized,
+    S1: nd::Data<Elem = Complex<T>>,
+    S2: nd::Data<Elem = Complex<T>>,
+{
+    optimize_d_fs(
+        new_state,
+        visibility_state,
+        &[depth, quantity],
+        updates_count,
+        rng,
+    )
+}
+
+//    ██████     ███    ███    ███████            ███    ███     ██████     ██████     ███████
+//   ██          ████  ████    ██                 ████  ████    ██    ██    ██   ██    ██
+//   ██   ███    ██ ████ ██    █████              ██ ████ ██    ██    ██    ██   ██    █████
+//   ██    ██    ██  ██  ██    ██                 ██  ██  ██    ██    ██    ██   ██    ██
+//    ██████     ██      ██    ███████            ██      ██     ██████     ██████     ███████
+
+/// Number of candidates each thread screens before accepted ones are applied to the
+/// state, when running with more than one thread.
+const CANDIDATE_BATCH_SIZE: usize = 256;
+
+/// Number of corrections after which `aa4` and `aa6` are recomputed from
+/// intermediate state instead of being updated incrementally.
+const FULL_UPDATE_INTERVAL: usize = 64;
+
+/// Bipartitions of three subsystems, each given by subsystems forming first block.
+const BIPARTITIONS_3P: [&[usize]; 3] = [&[0], &[1], &[2]];
+
+/// Bipartitions of four subsystems, both 1|3 and 2|2 cuts, given as in
+/// `BIPARTITIONS_3P`.
+const BIPARTITIONS_4P: [&[usize]; 7] =
+    [&[0], &[1], &[2], &[3], &[0, 1], &[0, 2], &[0, 3]];
+
+/// Subsystem order placing subsystems from `block` before all the remaining ones.
+fn bipartition_order(block: &[usize], quantity: usize) -> Vec<usize> {
+    let rest = (0..quantity).filter(|idx| !block.contains(idx));
+    block.iter().copied().chain(rest).collect()
+}
+
+/// Dimensions of both blocks of bipartition of subsystems into `block` and its
+/// complement.
+fn bipartition_dimensions(dimensions: &[usize], block: &[usize]) -> (usize, usize) {
+    let depth_1 = block.iter().map(|idx| dimensions[*idx]).product::<usize>();
+    (depth_1, dimensions.iter().product::<usize>() / depth_1)
+}
+
+fn permute_dimensions(dimensions: &[usize], order: &[usize]) -> Vec<usize> {
+    order.iter().map(|idx| dimensions[*idx]).collect()
+}
+
+fn inverse_order(order: &[usize]) -> Vec<usize> {
+    let mut inverse = vec![0; order.len()];
+    for (idx, subsystem) in order.iter().enumerate() {
+        inverse[*subsystem] = idx;
+    }
+    inverse
+}
+
+/// Empty candidates of every shape candidates of given mode can have, in biseparable
+/// modes one per bipartition.
+fn candidate_shapes<T>(mode: &AlgoMode, dimensions: &[usize]) -> Vec<ProductState<T>>
+where
+    T: Float + std::fmt::Debug + rand_distr::uniform::SampleUniform + 'static,
+{
+    match mode {
+        AlgoMode::FSnQd | AlgoMode::SBiPa => vec![ProductState::zeros(dimensions)],
+        AlgoMode::G3PaE3qD => BIPARTITIONS_3P
+            .iter()
+            .map(|block| ProductState::zeros_cut(dimensions, block))
+            .collect(),
+        AlgoMode::G4PaE3qD => BIPARTITIONS_4P
+            .iter()
+            .map(|block| ProductState::zeros_cut(dimensions, block))
+            .collect(),
+    }
+}
+
+/// Draw random candidate into one of `candidates` created with `candidate_shapes`,
+/// shape (bipartition) is chosen at random. Returns index of drawn candidate.
+fn random_candidate_into<T, R>(candidates: &mut [ProductState<T>], rng: &mut R) -> usize
+where
+    T: Float + std::fmt::Debug + rand_distr::uniform::SampleUniform + 'static,
+    R: Rng + ?Sized,
+{
+    let shape = if candidates.len() == 1 {
+        0
+    } else {
+        rng.gen_range(0..candidates.len())
+    };
+    candidates[shape].fill_random(rng);
+    shape
+}
+
+/// Scratch buffers owned by backend, allocated along with it, so that iterations of
+/// `run_epoch` do not allocate.
+#[derive(Clone)]
+struct Workspace<T> {
+    state: nd::Array2<Complex<T>>,
+    rotated: nd::Array2<Complex<T>>,
+    matrix_buffer: nd::Array2<Complex<T>>,
+    accumulated: nd::Array2<Complex<T>>,
+}
+
+impl<T> Workspace<T>
+where
+    T: Float + std::fmt::Debug + rand_distr::uniform::SampleUniform + 'static,
+{
+    fn new(size: usize) -> Self {
+        Self {
+            state: nd::Array2::zeros((size, size)),
+            rotated: nd::Array2::zeros((size, size)),
+            matrix_buffer: nd::Array2::zeros((size, size)),
+            accumulated: nd::Array2::zeros((size, size)),
+        }
+    }
+}
+
+/// Source of candidate states screened by `RustBackend`, holds the current candidate,
+/// either drawn with `sample` or set with `assign`. All random numbers have to be
+/// drawn from generator passed to `sample`, and no state other than scratch buffers
+/// may be kept between calls, so that runs are reproducible and can be restored from
+/// checkpoint.
+pub trait CandidateSampler<T>: Clone + Send + Sized {
+    /// Candidate state, sent between threads when screening in parallel.
+    type Candidate: Clone + Send;
+
+    /// Create sampler of candidates for system composed of subsystems with
+    /// `dimensions` searched in given `mode`.
+    fn new(mode: AlgoMode, dimensions: &[usize]) -> Result<Self>;
+
+    /// Replace current candidate with random one.
+    fn sample<R>(&mut self, rng: &mut R)
+    where
+        R: Rng + ?Sized;
+
+    /// Current candidate, as drawn by last call to `sample` or `assign`.
+    fn candidate(&self) -> &Self::Candidate;
+
+    /// Replace current candidate with copy of `candidate`.
+    fn assign(&mut self, candidate: &Self::Candidate);
+
+    /// Same as `product` of density matrix of current candidate and `operator`.
+    fn product<S>(&mut self, operator: &nd::ArrayBase<S, nd::Ix2>) -> T
+    where
+        S: nd::Data<Elem = Complex<T>>;
+}
+
+/// Local optimization of candidates `C` accepted by `RustBackend`, applied before
+/// candidate is mixed into intermediate state. Same restrictions as for
+/// `CandidateSampler` apply.
+pub trait CandidateOptimizer<T, C>: Clone + Send + Sized {
+    /// Create optimizer for system composed of subsystems with `dimensions` searched in
+    /// given `mode`.
+    fn new(mode: AlgoMode, dimensions: &[usize]) -> Result<Self>;
+
+    /// Optimize `candidate` increasing its product with `visibility_state` in
+    /// `updates_count` steps, writing density matrix of optimized state into `out`.
+    fn optimize<R, S>(
+        &mut self,
+        candidate: &C,
+        visibility_state: &nd::ArrayBase<S, nd::Ix2>,
+        updates_count: usize,
+        rng: &mut R,
+        out: &mut nd::Array2<Complex<T>>,
+    ) where
+        R: Rng + ?Sized,
+        S: nd::Data<Elem = Complex<T>>;
+}
+
+/// Default `CandidateSampler`, draws random product states, in biseparable modes
+/// separable with respect to bipartition chosen at random.
+#[derive(Clone, Debug)]
+pub struct ProductSampler<T> {
+    candidates: Vec<ProductState<T>>,
+    current: usize,
+    vector: nd::Array1<Complex<T>>,
+    buffer: nd::Array1<Complex<T>>,
+}
+
+impl<T> CandidateSampler<T> for ProductSampler<T>
+where
+    T: Float + std::fmt::Debug + rand_distr::uniform::SampleUniform + Send + 'static,
+{
+    type Candidate = ProductState<T>;
+
+    fn new(mode: AlgoMode, dimensions: &[usize]) -> Result<Self> {
+        let size = dimensions.iter().product::<usize>();
+        Ok(Self {
+            candidates: candidate_shapes(&mode, dimensions),
+            current: 0,
+            vector: nd::Array1::zeros(size),
+            buffer: nd::Array1::zeros(size),
+        })
+    }
+
+    fn sample<R>(&mut self, rng: &mut R)
+    where
+        R: Rng + ?Sized,
+    {
+        self.current = random_candidate_into(&mut self.candidates, rng);
+    }
+
+    fn candidate(&self) -> &ProductState<T> {
+        &self.candidates[self.current]
+    }
+
+    fn assign(&mut self, candidate: &ProductState<T>) {
+        self.current = shape_index(&self.candidates, candidate);
+        self.candidates[self.current].assign(candidate);
+    }
+
+    fn product<S>(&mut self, operator: &nd::ArrayBase<S, nd::Ix2>) -> T
+    where
+        S: nd::Data<Elem = Complex<T>>,
+    {
+        self.candidates[self.current].product_into(
+            operator,
+            &mut self.vector,
+            &mut self.buffer,
+        )
+    }
+}
+
+/// Default `CandidateOptimizer`, rotates local factors of product states with
+/// `optimize_product_into`.
+#[derive(Clone, Debug)]
+pub struct ProductOptimizer<T> {
+    optimizers: Vec<ProductWorkspace<T>>,
+    vector: nd::Array1<Complex<T>>,
+    buffer: nd::Array1<Complex<T>>,
+}
+
+impl<T> CandidateOptimizer<T, ProductState<T>> for ProductOptimizer<T>
+where
+    T: Float + std::fmt::Debug + rand_distr::uniform::SampleUniform + Send + 'static,
+{
+    fn new(mode: AlgoMode, dimensions: &[usize]) -> Result<Self> {
+        let size = dimensions.iter().product::<usize>();
+        Ok(Self {
+            optimizers: candidate_shapes(&mode, dimensions)
+                .iter()
+                .map(ProductWorkspace::new)
+                .collect(),
+            vector: nd::Array1::zeros(size),
+            buffer: nd::Array1::zeros(size),
+        })
+    }
+
+    fn optimize<R, S>(
+        &mut self,
+        candidate: &ProductState<T>,
+        visibility_state: &nd::ArrayBase<S, nd::Ix2>,
+        updates_count: usize,
+        rng: &mut R,
+        out: &mut nd::Array2<Complex<T>>,
+    ) where
+        R: Rng + ?Sized,
+        S: nd::Data<Elem = Complex<T>>,
+    {
+        let optimizer = self
+            .optimizers
+            .iter_mut()
+            .find(|optimizer| optimizer.optimized().has_shape_of(candidate))
+            .expect("Candidate of shape not supported by optimizer.");
+        optimize_product_into(
+            candidate,
+            visibility_state,
+            updates_count,
+            rng,
+            optimizer,
+        );
+        // Projector is only built once the candidate is optimized and about to be
+        // mixed into intermediate state.
+        optimizer
+            .optimized()
+            .projector_into(out, &mut self.vector, &mut self.buffer);
+    }
+}
+
+/// Index of state in `candidates` with the same shape as `candidate`.
+fn shape_index<T>(candidates: &[ProductState<T>], candidate: &ProductState<T>) -> usize
+where
+    T: Float + std::fmt::Debug + rand_distr::uniform::SampleUniform + 'static,
+{
+    candidates
+        .iter()
+        .position(|other| other.has_shape_of(candidate))
+        .expect("Candidate of shape not supported by sampler.")
+}
+
+/// Candidate generator used by single thread, its own stream of random numbers and
+/// sampler with buffers for screening candidates.
+#[derive(Clone)]
+struct CandidateStream<C> {
+    rng: BackendRng,
+    sampler: C,
+}
+
+/// Candidates passing screening of single batch, in iteration order. Slots past
+/// `count` are kept, so that candidates of later batches are copied into existing
+/// buffers instead of being allocated.
+struct Screened<C> {
+    count: usize,
+    candidates: Vec<(i64, C)>,
+}
+
+impl<C> Default for Screened<C> {
+    fn default() -> Self {
+        Self {
+            count: 0,
+            candidates: Vec::new(),
+        }
+    }
+}
+
+impl<C: Clone> Screened<C> {
+    fn clear(&mut self) {
+        self.count = 0;
+    }
+
+    fn push(&mut self, iteration_index: i64, candidate: &C) {
+        match self.candidates.get_mut(self.count) {
+            Some(slot) => {
+                slot.0 = iteration_index;
+                slot.1.clone_from(candidate);
+            }
+            None => self.candidates.push((iteration_index, candidate.clone())),
+        }
+        self.count += 1;
+    }
+
+    fn iter(&self) -> std::slice::Iter<'_, (i64, C)> {
+        self.candidates[..self.count].iter()
+    }
+}
+
+/// Request to screen candidates of iterations `first..last` against snapshot of
+/// `visibility_reduced`, candidates with product greater than `dd1` are written to
+/// `screened`, which is sent back.
+struct ScreeningRequest<T, C> {
+    first: i64,
+    last: i64,
+    dd1: T,
+    screened: Screened<C>,
+}
+
+/// Thread screening candidates drawn from single `CandidateStream`, kept alive for the
+/// whole epoch so that threads are not spawned for every batch of candidates.
+struct CandidateWorker<T, C> {
+    requests: mpsc::SyncSender<ScreeningRequest<T, C>>,
+    results: mpsc::Receiver<Screened<C>>,
+}
+
+impl<T, C> CandidateWorker<T, C>
+where
+    T: Float + Send + Sync + 'static,
+    C: Clone + Send,
+{
+    /// Spawn worker drawing candidates from `stream` in `scope` and screening them
+    /// against `snapshot`, worker exits once it is dropped.
+    fn spawn<'scope, S>(
+        scope: &'scope thread::Scope<'scope, '_>,
+        stream: &'scope mut CandidateStream<S>,
+        snapshot: &'scope RwLock<nd::Array2<Complex<T>>>,
+    ) -> Self
+    where
+        S: CandidateSampler<T, Candidate = C>,
+        C: 'scope,
+    {
+        // Only one request is in flight at a time, so bounded channels never block
+        // and, unlike unbounded ones, do not allocate on send.
+        let (requests, request_receiver) =
+            mpsc::sync_channel::<ScreeningRequest<T, C>>(1);
+        let (result_sender, results) = mpsc::sync_channel(1);
+
+        scope.spawn(move || {
+            let CandidateStream { rng, sampler } = stream;
+            for mut request in request_receiver {
+                let visibility_reduced =
+                    snapshot.read().expect("Candidate thread panicked.");
+                request.screened.clear();
+                for iteration_index in request.first..request.last {
+                    sampler.sample(rng);
+                    if sampler.product(&*visibility_reduced) > request.dd1 {
+                        request.screened.push(iteration_index, sampler.candidate());
+                    }
+                }
+                drop(visibility_reduced);
+                if result_sender.send(request.screened).is_err() {
+                    break;
+                }
+            }
+        });
+        Self { requests, results }
+    }
+}
+
 /*
  ████   ███   ████ █   █ █████ █    █ ████      ████ █      ███   ████  ████
  █   █ █   █ █     █  █  █     ██   █ █   █    █     █     █   █ █     █
@@ -284,78 +1441,145 @@ where
  ████  █   █  ████ █   █ █████ █   ██ ████      ████ █████ █   █ ████  ████
 */
 
+/// Gilbert algorithm working on dense density matrices, candidates are drawn by
+/// sampler `C` and optimized by `O`.
 #[derive(Clone)]
-pub struct RustBackend<T> {
+pub struct RustBackend<T, C = ProductSampler<T>, O = ProductOptimizer<T>> {
     initial: nd::Array2<Complex<T>>,
-    depth: usize,
-    quantity: usize,
+    dimensions: Vec<usize>,
 
     visibility: nd::Array2<Complex<T>>,
     intermediate: nd::Array2<Complex<T>>,
     visibility_reduced: nd::Array2<Complex<T>>,
 
-    symmetries: Option<Vec<Vec<nd::Array2<Complex<T>>>>>,
+    symmetries: Option<Symmetries<T>>,
     projection: Option<nd::Array2<Complex<T>>>,
 
+    aa1: T,
     aa4: T,
     aa6: T,
     dd1: T,
 
     corrections: Vec<(usize, usize, T)>,
+    rng: BackendRng,
+    // Separate streams of candidate generators, used only when running on more than
+    // one thread.
+    streams: Vec<CandidateStream<C>>,
+    sampler: C,
+    optimizer: O,
+    workspace: Workspace<T>,
     // Specified at the very bottom to match construction argument order. It can not
     // be passed during construction before `optimize_callback` as it uses match on mode
     // the mode otherwise would be moved, thus requiring a clone.
     mode: AlgoMode,
 }
 
-impl<T> fmt::Debug for RustBackend<T>
+impl<T, C, O> fmt::Debug for RustBackend<T, C, O>
 where
     T: Float + std::fmt::Debug + rand_distr::uniform::SampleUniform + 'static,
 {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(
             f,
-            "RustBackend<{}>(depth: {}, quantity: {}, state-size: {})",
+            "RustBackend<{}>(dimensions: {:?}, state-size: {})",
             any::type_name::<T>(),
-            self.depth,
-            self.quantity,
+            self.dimensions,
             self.initial.dim().0
         )
     }
 }
 
-impl<T> RustBackend<T>
+impl<T, C, O> RustBackend<T, C, O>
 where
-    T: Float + std::fmt::Debug + rand_distr::uniform::SampleUniform + 'static,
+    T: Float
+        + std::fmt::Debug
+        + rand_distr::uniform::SampleUniform
+        + Send
+        + Sync
+        + 'static,
+    C: CandidateSampler<T>,
+    O: CandidateOptimizer<T, C::Candidate>,
 {
-    pub fn new(
-        initial: &nd::Array2<Complex<T>>,
+    /// Create backend for `quantity` subsystems of dimension `depth` each. In SBiPa
+    /// mode `depth` and `quantity` are instead dimensions of the two subsystems.
+    pub fn new<S>(
+        initial: &nd::ArrayBase<S, nd::Ix2>,
         depth: usize,
         quantity: usize,
         mode: AlgoMode,
         visibility: T,
-    ) -> Self
+        seed: Option<u64>,
+    ) -> Result<Self>
+    where
+        S: nd::Data<Elem = Complex<T>>,
+    {
+        let dimensions = mode.dimensions(depth, quantity);
+        Self::with_dimensions(initial, &dimensions, mode, visibility, seed)
+    }
+
+    /// Create backend for subsystems with (possibly different) dimensions given by
+    /// `dimensions`, their product must match size of `initial` state. Backend seeded
+    /// with the same `seed` produces the same corrections, without seed it draws
+    /// seed from system entropy.
+    pub fn with_dimensions<S>(
+        initial: &nd::ArrayBase<S, nd::Ix2>,
+        dimensions: &[usize],
+        mode: AlgoMode,
+        visibility: T,
+        seed: Option<u64>,
+    ) -> Result<Self>
     where
-        T: Float + std::fmt::Debug + rand_distr::uniform::SampleUniform + 'static,
+        S: nd::Data<Elem = Complex<T>>,
     {
-        let visibility_matrix =
-            RustBackend::create_visibility_matrix(initial, visibility);
-        let intermediate_matrix =
-            RustBackend::create_intermediate_state(&visibility_matrix);
+        ensure_square(&initial.view(), "Initial state")?;
+
+        if dimensions.is_empty() || dimensions.contains(&0) {
+            return Err(Error::Value(format!(
+                "Subsystem dimensions {:?} have to be non-empty and positive.",
+                dimensions
+            )));
+        }
+        if system_size(dimensions) != Some(initial.dim().0) {
+            return Err(Error::Value(format!(
+                "Product of subsystem dimensions {:?} does not match state of shape \
+                 {:?}.",
+                dimensions,
+                initial.dim()
+            )));
+        }
+
+        let subsystems_required = match mode {
+            AlgoMode::FSnQd => None,
+            AlgoMode::SBiPa => Some(2),
+            AlgoMode::G3PaE3qD => Some(3),
+            AlgoMode::G4PaE3qD => Some(4),
+        };
+        if let Some(subsystems_required) = subsystems_required {
+            if dimensions.len() != subsystems_required {
+                return Err(Error::Value(format!(
+                    "Mode '{}' requires {} subsystems, got {}.",
+                    mode.name(),
+                    subsystems_required,
+                    dimensions.len()
+                )));
+            }
+        }
+
+        let visibility_matrix = Self::create_visibility_matrix(initial, visibility);
+        let intermediate_matrix = Self::create_intermediate_state(&visibility_matrix);
 
         let visibility_reduced =
             visibility_matrix.view().sub(&intermediate_matrix.view());
 
+        let aa1 = product(&visibility_matrix, &visibility_matrix);
         let aa4 =
             T::from(2).unwrap() * product(&visibility_matrix, &intermediate_matrix);
         let aa6 = product(&intermediate_matrix, &intermediate_matrix);
         let dd1 = product(&intermediate_matrix, &visibility_reduced);
 
-        RustBackend {
+        Ok(RustBackend {
             initial: initial.to_owned(),
-
-            depth,
-            quantity,
+            dimensions: dimensions.to_vec(),
 
             visibility: visibility_matrix,
             intermediate: intermediate_matrix,
@@ -365,19 +1589,28 @@ where
             projection: None,
 
             corrections: vec![],
+            rng: rng_from_seed(seed),
+            streams: vec![],
+            sampler: C::new(mode, dimensions)?,
+            optimizer: O::new(mode, dimensions)?,
+            workspace: Workspace::new(initial.dim().0),
 
+            aa1,
             aa4,
             aa6,
             dd1,
 
             mode,
-        }
+        })
     }
 
-    fn create_visibility_matrix(
-        initial: &nd::Array2<Complex<T>>,
+    fn create_visibility_matrix<S>(
+        initial: &nd::ArrayBase<S, nd::Ix2>,
         visibility: T,
-    ) -> nd::Array2<Complex<T>> {
+    ) -> nd::Array2<Complex<T>>
+    where
+        S: nd::Data<Elem = Complex<T>>,
+    {
         let length_of_first_axis = initial.dim().0;
         let array_size_complex =
             Complex::<T>::new(T::from(length_of_first_axis).unwrap(), T::zero());
@@ -409,86 +1642,101 @@ where
         intermediate_state
     }
 
-    pub fn set_symmetries(&mut self, symmetries: Vec<Vec<nd::Array2<Complex<T>>>>) {
-        self.symmetries = Some(symmetries);
-    }
-
-    pub fn get_state(&self) -> &nd::Array2<Complex<T>> {
-        &self.intermediate
-    }
-
-    pub fn get_corrections(&self) -> &Vec<(usize, usize, T)> {
-        &self.corrections
+    fn progress(&self, iteration: i64) -> Progress<T> {
+        Progress {
+            iteration,
+            corrections: self.corrections.len(),
+            distance: self.corrections.last().map(|correction| correction.2),
+        }
     }
 
-    pub fn run_epoch(&mut self, iterations: i64, epoch_index: usize) {
-        let depth = self.depth;
-        let quantity = self.quantity;
-        let epochs = 20 * depth * depth * quantity;
-
-        for iteration_index in 0..iterations {
-            let alternative_state = match self.mode {
-                AlgoMode::FSnQd => random_d_fs(depth, quantity),
-                AlgoMode::SBiPa => panic!("Mode 'SBiPa' is currently not supported."),
-                AlgoMode::G3PaE3qD => {
-                    panic!("Mode 'G3PaE3qD' is currently not supported.")
-                }
-                AlgoMode::G4PaE3qD => {
-                    panic!("Mode 'G4PaE3qD' is currently not supported.")
-                }
-            };
-
-            if product(&alternative_state, &self.visibility_reduced) > self.dd1 {
-                self.update_state(
-                    &alternative_state,
-                    iterations,
-                    epoch_index,
-                    epochs,
-                    iteration_index,
-                );
-            }
+    /// Generate candidates for iterations from `start` to `end` in parallel, split
+    /// evenly between `workers`. Candidates passing screening against current state
+    /// are written to `screened`, one buffer per worker, so that they are ordered by
+    /// iteration when buffers are concatenated.
+    fn screen_candidates(
+        &self,
+        workers: &[CandidateWorker<T, C::Candidate>],
+        snapshot: &RwLock<nd::Array2<Complex<T>>>,
+        screened: &mut [Screened<C::Candidate>],
+        start: i64,
+        end: i64,
+    ) {
+        // Workers hold read lock only between receiving request and sending results,
+        // so it is always free here.
+        snapshot
+            .write()
+            .expect("Candidate thread panicked.")
+            .assign(&self.visibility_reduced);
+        let chunk = (end - start + workers.len() as i64 - 1) / workers.len() as i64;
+
+        for (thread_index, (worker, screened)) in
+            workers.iter().zip(screened.iter_mut()).enumerate()
+        {
+            let first = end.min(start + thread_index as i64 * chunk);
+            worker
+                .requests
+                .send(ScreeningRequest {
+                    first,
+                    last: end.min(first + chunk),
+                    dd1: self.dd1,
+                    screened: std::mem::take(screened),
+                })
+                .expect("Candidate thread panicked.");
+        }
+        for (worker, screened) in workers.iter().zip(screened.iter_mut()) {
+            *screened = worker.results.recv().expect("Candidate thread panicked.");
         }
     }
 
+    /// Optimize current candidate of the sampler and mix it into intermediate state.
     fn update_state(
         &mut self,
-        alternative_state: &ndarray::Array2<Complex<T>>,
         iterations: i64,
         epoch_index: usize,
         epochs: usize,
         iteration_index: i64,
     ) {
-        let depth = self.depth;
-        let quantity = self.quantity;
         let literal_two = T::from(2).unwrap();
 
-        let optimized_state = match self.mode {
-            AlgoMode::FSnQd => optimize_d_fs(
-                alternative_state,
-                &self.visibility_reduced,
-                depth,
-                quantity,
-                epochs,
-            ),
-            AlgoMode::SBiPa => panic!("Mode 'SBiPa' is currently not supported."),
-            AlgoMode::G3PaE3qD => {
-                panic!("Mode 'G3PaE3qD' is currently not supported.")
-            }
-            AlgoMode::G4PaE3qD => {
-                panic!("Mode 'G4PaE3qD' is currently not supported.")
-            }
-        };
+        let Workspace {
+            state: optimized_state,
+            rotated,
+            matrix_buffer,
+            accumulated,
+        } = &mut self.workspace;
+
+        self.optimizer.optimize(
+            self.sampler.candidate(),
+            &self.visibility_reduced,
+            epochs,
+            &mut self.rng,
+            optimized_state,
+        );
 
         if let Some(ref symmetries) = self.symmetries {
-            self.intermediate = apply_symmetries(&self.intermediate, symmetries);
+            match symmetries {
+                Symmetries::Sequential(rows) => {
+                    apply_symmetries_into(optimized_state, rows, matrix_buffer, rotated)
+                }
+                Symmetries::Group(group) => apply_symmetry_group_into(
+                    optimized_state,
+                    group,
+                    matrix_buffer,
+                    rotated,
+                    accumulated,
+                ),
+            };
         }
         if let Some(ref projection) = self.projection {
-            self.intermediate = rotate(&self.intermediate, projection);
+            rotate_into(&*optimized_state, projection, matrix_buffer, rotated);
+            std::mem::swap(optimized_state, rotated);
         }
+        let optimized_state = &*optimized_state;
 
-        let aa3 = product(&optimized_state, &optimized_state);
-        let aa2 = literal_two * product(&self.visibility, &optimized_state);
-        let aa5 = literal_two * product(&self.intermediate, &optimized_state);
+        let aa3 = product(optimized_state, optimized_state);
+        let aa2 = literal_two * product(&self.visibility, optimized_state);
+        let aa5 = literal_two * product(&self.intermediate, optimized_state);
 
         let bb2 = -self.aa4 + aa2 + aa5 - (literal_two * aa3);
         let bb3 = self.aa6 - aa5 + aa3;
@@ -496,20 +1744,418 @@ where
 
         if T::zero() <= cc1 && cc1 <= T::one() {
             let cc1_inverse = T::one() - cc1;
-            self.intermediate = self.intermediate.mapv(|x| x * cc1)
-                + alternative_state.mapv(|x| x * cc1_inverse);
-
-            self.visibility_reduced = &self.visibility - &self.intermediate;
-
-            self.aa4 = literal_two * product(&self.visibility, &self.intermediate);
-            self.aa6 = product(&self.intermediate, &self.intermediate);
+            nd::Zip::from(&mut self.intermediate)
+                .and(optimized_state)
+                .for_each(|x, y| *x = *x * cc1 + *y * cc1_inverse);
+
+            nd::Zip::from(&mut self.visibility_reduced)
+                .and(&self.visibility)
+                .and(&self.intermediate)
+                .for_each(|x, visibility, intermediate| {
+                    *x = *visibility - *intermediate
+                });
+
+            // Both products are bilinear, so they follow from products with the
+            // optimized state already computed above. Rounding errors accumulate
+            // though, hence every once in a while they are computed from scratch.
+            if (self.corrections.len() + 1) % FULL_UPDATE_INTERVAL == 0 {
+                self.aa4 = literal_two * product(&self.visibility, &self.intermediate);
+                self.aa6 = product(&self.intermediate, &self.intermediate);
+            } else {
+                self.aa4 = cc1 * self.aa4 + cc1_inverse * aa2;
+                self.aa6 = cc1 * cc1 * self.aa6
+                    + cc1 * cc1_inverse * aa5
+                    + cc1_inverse * cc1_inverse * aa3;
+            }
             self.dd1 = self.aa4 / literal_two - self.aa6;
 
+            // Expansion of product(&self.visibility_reduced, &self.visibility_reduced).
             self.corrections.push((
                 (epoch_index * iterations as usize + iteration_index as usize + 1),
                 self.corrections.len() + 1,
-                product(&self.visibility_reduced, &self.visibility_reduced),
+                self.aa1 - self.aa4 + self.aa6,
+            ));
+        }
+    }
+}
+
+impl<T, C, O> Backend<T> for RustBackend<T, C, O>
+where
+    T: Float
+        + std::fmt::Debug
+        + rand_distr::uniform::SampleUniform
+        + Send
+        + Sync
+        + 'static,
+    C: CandidateSampler<T>,
+    O: CandidateOptimizer<T, C::Candidate>,
+{
+    fn new(
+        initial: &nd::ArrayView2<Complex<T>>,
+        dimensions: &[usize],
+        mode: AlgoMode,
+        visibility: T,
+        seed: Option<u64>,
+    ) -> Result<Self> {
+        Self::with_dimensions(initial, dimensions, mode, visibility, seed)
+    }
+
+    /// Set symmetries applied to every optimized candidate, `mode` determines whether
+    /// they are applied one after another or the candidate is averaged over group
+    /// they form. In group modes all symmetries have to be unitary.
+    fn set_symmetries(
+        &mut self,
+        symmetries: Vec<Vec<nd::Array2<Complex<T>>>>,
+        mode: SymmetryMode,
+    ) -> Result<()> {
+        let state_size = self.initial.dim().0;
+
+        for (row_index, row) in symmetries.iter().enumerate() {
+            for (column_index, symmetry) in row.iter().enumerate() {
+                ensure_same_shape(
+                    &symmetry.view(),
+                    &self.initial.view(),
+                    &format!("Symmetry [{}][{}]", row_index, column_index),
+                )?;
+                if !matches!(mode, SymmetryMode::Sequential) && !is_unitary(symmetry) {
+                    return Err(Error::Value(format!(
+                        "Symmetry [{}][{}] is not unitary.",
+                        row_index, column_index
+                    )));
+                }
+            }
+        }
+
+        let symmetries = match mode {
+            SymmetryMode::Sequential => Symmetries::Sequential(symmetries),
+            SymmetryMode::Generators => {
+                let generators = symmetries.into_iter().flatten().collect::<Vec<_>>();
+                Symmetries::Group(generate_group(&generators, state_size)?)
+            }
+            SymmetryMode::Group => {
+                let elements = symmetries.into_iter().flatten().collect::<Vec<_>>();
+                if !is_group(&elements) {
+                    return Err(Error::Value(
+                        "Symmetries are not closed under multiplication.".into(),
+                    ));
+                }
+                Symmetries::Group(elements)
+            }
+        };
+        if let Symmetries::Group(group) = &symmetries {
+            if !is_averaging_invariant(group, state_size) {
+                return Err(Error::Value(
+                    "State averaged over symmetries is not invariant.".into(),
+                ));
+            }
+        }
+        self.symmetries = Some(symmetries);
+        Ok(())
+    }
+
+    /// Set projection applied to every optimized candidate before it is mixed into
+    /// intermediate state. Projection has to be square matrix of the same size as the
+    /// state.
+    fn set_projection(&mut self, projection: nd::Array2<Complex<T>>) -> Result<()> {
+        ensure_same_shape(&projection.view(), &self.initial.view(), "Projection")?;
+        self.projection = Some(projection);
+        Ok(())
+    }
+
+    /// Set number of threads generating and screening candidates. With more than one
+    /// thread every thread draws candidates from its own stream of backend random
+    /// generator, so corrections are reproducible for given seed and thread count, but
+    /// differ from single threaded run. State updates are always applied sequentially.
+    fn set_threads(&mut self, threads: usize) -> Result<()> {
+        if threads == 0 {
+            return Err(Error::Value(
+                "Number of threads has to be positive.".to_string(),
             ));
         }
+        self.streams = if threads == 1 {
+            vec![]
+        } else {
+            (0..threads)
+                .map(|thread_index| {
+                    let mut rng = self.rng.clone();
+                    rng.set_stream(thread_index as u64 + 1);
+                    CandidateStream {
+                        rng,
+                        sampler: self.sampler.clone(),
+                    }
+                })
+                .collect()
+        };
+        Ok(())
+    }
+
+    fn get_threads(&self) -> usize {
+        self.streams.len().max(1)
+    }
+
+    fn get_dimensions(&self) -> &[usize] {
+        &self.dimensions
+    }
+
+    fn get_state(&self) -> &nd::Array2<Complex<T>> {
+        &self.intermediate
+    }
+
+    fn get_corrections(&self) -> &[(usize, usize, T)] {
+        &self.corrections
+    }
+
+    fn run_epoch_with<B, F>(
+        &mut self,
+        iterations: i64,
+        epoch_index: usize,
+        interval: i64,
+        mut callback: F,
+    ) -> ControlFlow<B>
+    where
+        F: FnMut(Progress<T>) -> ControlFlow<B>,
+    {
+        assert!(interval > 0, "Progress interval has to be positive.");
+        let epochs = 20
+            * self
+                .dimensions
+                .iter()
+                .map(|depth| depth * depth)
+                .sum::<usize>();
+
+        if self.streams.is_empty() {
+            for iteration_index in 0..iterations {
+                self.sampler.sample(&mut self.rng);
+
+                if self.sampler.product(&self.visibility_reduced) > self.dd1 {
+                    self.update_state(iterations, epoch_index, epochs, iteration_index);
+                }
+                if (iteration_index + 1) % interval == 0 {
+                    callback(self.progress(iteration_index + 1))?;
+                }
+            }
+            return ControlFlow::Continue(());
+        }
+
+        // Streams are lent to workers for the whole epoch and put back once all of
+        // them exit, also when callback stops the epoch early.
+        let mut streams = std::mem::take(&mut self.streams);
+        // Snapshot of state screened against and buffers for screened candidates
+        // are allocated once per epoch and reused by all its batches.
+        let snapshot = RwLock::new(self.visibility_reduced.clone());
+        let result = thread::scope(|scope| {
+            let workers = streams
+                .iter_mut()
+                .map(|stream| CandidateWorker::spawn(scope, stream, &snapshot))
+                .collect::<Vec<_>>();
+            let mut screened = workers
+                .iter()
+                .map(|_| Screened::default())
+                .collect::<Vec<_>>();
+
+            let batch_size = (workers.len() * CANDIDATE_BATCH_SIZE) as i64;
+            let mut batch_start = 0;
+            // Iteration at which callback is due next, in threaded mode candidates are
+            // processed out of iteration order so it can not be derived from index
+            // alone.
+            let mut reported = interval;
+
+            while batch_start < iterations {
+                let batch_end = iterations.min(batch_start + batch_size);
+
+                self.screen_candidates(
+                    &workers,
+                    &snapshot,
+                    &mut screened,
+                    batch_start,
+                    batch_end,
+                );
+                for &(iteration_index, ref candidate) in
+                    screened.iter().flat_map(Screened::iter)
+                {
+                    while reported <= iteration_index {
+                        callback(self.progress(reported))?;
+                        reported += interval;
+                    }
+                    self.sampler.assign(candidate);
+
+                    // Candidates were screened against state from before the batch,
+                    // updates applied meanwhile may have made them obsolete.
+                    if self.sampler.product(&self.visibility_reduced) > self.dd1 {
+                        self.update_state(
+                            iterations,
+                            epoch_index,
+                            epochs,
+                            iteration_index,
+                        );
+                    }
+                }
+                while reported <= batch_end {
+                    callback(self.progress(reported))?;
+                    reported += interval;
+                }
+                batch_start = batch_end;
+            }
+            ControlFlow::Continue(())
+        });
+        self.streams = streams;
+        result
+    }
+
+    fn to_bytes(&self) -> Result<Vec<u8>> {
+        Ok(RustBackend::to_bytes(self))
+    }
+
+    fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        RustBackend::from_bytes(bytes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Purity of reduced state of first block of `state`, 1 for pure states
+    /// separable across the cut between its two factors.
+    fn cut_purity(state: &ProductState<f64>, vector: &nd::Array1<Complex<f64>>) -> f64 {
+        let depths = state.factor_dimensions();
+        let tensor = vector
+            .view()
+            .into_shape(nd::IxDyn(&state.dimensions))
+            .unwrap()
+            .permuted_axes(nd::IxDyn(&state.order));
+        let matrix = tensor
+            .as_standard_layout()
+            .into_shape((depths[0], depths[1]))
+            .unwrap()
+            .to_owned();
+        let reduced = matrix.dot(&matrix.t().mapv(|x| x.conj()));
+        reduced.iter().map(|x| x.norm_sqr()).sum()
+    }
+
+    /// Check that candidates drawn in biseparable `mode` are pure, separable across
+    /// their cut, that every cut is drawn and that the optimizer keeps them separable.
+    fn assert_candidates_separable(mode: AlgoMode, dimensions: &[usize], cuts: usize) {
+        let size = dimensions.iter().product::<usize>();
+        let mut rng = rng_from_seed(Some(0));
+        let mut sampler = ProductSampler::<f64>::new(mode, dimensions).unwrap();
+        let mut optimizer = ProductOptimizer::<f64>::new(mode, dimensions).unwrap();
+        let visibility_state = ProductState::random(&[size], &mut rng).projector();
+        let mut optimized = nd::Array2::zeros((size, size));
+        let mut shapes = vec![];
+
+        for _ in 0..100 {
+            sampler.sample(&mut rng);
+            let candidate = sampler.candidate();
+            assert_eq!(candidate.factors.len(), 2);
+            let shape = (candidate.order.clone(), candidate.factor_dimensions());
+            if !shapes.contains(&shape) {
+                shapes.push(shape);
+            }
+
+            let vector = candidate.vector();
+            assert!(
+                (vector.iter().map(|x| x.norm_sqr()).sum::<f64>() - 1.0).abs() < 1e-9
+            );
+            assert!((cut_purity(candidate, &vector) - 1.0).abs() < 1e-9);
+
+            optimizer.optimize(
+                candidate,
+                &visibility_state,
+                10,
+                &mut rng,
+                &mut optimized,
+            );
+            // Optimized state is pure, so any non-zero column is its state vector.
+            let column = (0..size)
+                .max_by(|lhs, rhs| {
+                    optimized[(*lhs, *lhs)]
+                        .re
+                        .total_cmp(&optimized[(*rhs, *rhs)].re)
+                })
+                .unwrap();
+            let vector = optimized
+                .column(column)
+                .mapv(|x| x / optimized[(column, column)].sqrt());
+            assert!((product(&optimized, &optimized) - 1.0).abs() < 1e-9);
+            assert!((cut_purity(candidate, &vector) - 1.0).abs() < 1e-9);
+        }
+        assert_eq!(shapes.len(), cuts);
+    }
+
+    #[test]
+    fn averaging_over_group_is_invariant() {
+        let mut rng = rng_from_seed(Some(0));
+        let unitary = random_unitary_d_fs::<f64, _>(&[2, 2], 1, &mut rng);
+        let identity = nd::Array2::eye(4);
+        assert!(is_averaging_invariant(std::slice::from_ref(&identity), 4));
+        assert!(!is_averaging_invariant(&[identity, unitary], 4));
+
+        let swap = nd::Array2::from_shape_fn((4, 4), |(i, j)| {
+            let swapped = (i % 2) * 2 + i / 2;
+            Complex::new(if j == swapped { 1.0 } else { 0.0 }, 0.0)
+        });
+        assert!(is_averaging_invariant(
+            &generate_group(&[swap], 4).unwrap(),
+            4
+        ));
+    }
+
+    #[test]
+    fn incremental_products_match_full_recompute() {
+        let mut vector = nd::Array1::<Complex<f64>>::zeros(8);
+        vector[0] = Complex::new(0.5f64.sqrt(), 0.0);
+        vector[7] = Complex::new(0.5f64.sqrt(), 0.0);
+        let state = project(&vector);
+        let mut backend = <RustBackend<f64> as Backend<f64>>::new(
+            &state.view(),
+            &[2, 2, 2],
+            AlgoMode::FSnQd,
+            0.4,
+            Some(3),
+        )
+        .unwrap();
+
+        // Run single iterations, so that every correction is checked right after it
+        // is recorded, both between and past full recomputes.
+        let mut epoch_index = 0;
+        while backend.corrections.len() < 3 * FULL_UPDATE_INTERVAL {
+            let count = backend.corrections.len();
+            backend.run_epoch(1, epoch_index);
+            epoch_index += 1;
+            assert!(epoch_index < 100_000, "Too few corrections were accepted.");
+            if backend.corrections.len() == count {
+                continue;
+            }
+
+            let (_, _, value) = *backend.corrections.last().unwrap();
+            let expected =
+                product(&backend.visibility_reduced, &backend.visibility_reduced);
+            assert!(
+                (value - expected).abs() < 1e-10,
+                "{} != {}",
+                value,
+                expected
+            );
+            let expected = product(&backend.intermediate, &backend.visibility_reduced);
+            assert!(
+                (backend.dd1 - expected).abs() < 1e-10,
+                "{} != {}",
+                backend.dd1,
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn g3pae3qd_candidates_separable() {
+        assert_candidates_separable(AlgoMode::G3PaE3qD, &[3, 3, 3], 3);
+        assert_candidates_separable(AlgoMode::G3PaE3qD, &[2, 3, 4], 3);
+    }
+
+    #[test]
+    fn g4pae3qd_candidates_separable() {
+        assert_candidates_separable(AlgoMode::G4PaE3qD, &[2, 2, 2, 2], 7);
+        assert_candidates_separable(AlgoMode::G4PaE3qD, &[2, 3, 2, 4], 7);
     }
 }
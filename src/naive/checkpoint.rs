@@ -0,0 +1,491 @@
+// Copyright 2023 Krzysztof Wisniewski <argmaster.world@gmail.com>
+//
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy of this
+// software and associated documentation files (the “Software”), to deal in the Software
+// without restriction, including without limitation the rights to use, copy, modify,
+// merge, publish, distribute, sublicense, and/or sell copies of the Software, and to
+// permit persons to whom the Software is furnished to do so, subject to the following
+// conditions:
+//
+// The above copyright notice and this permission notice shall be included in all copies
+// or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED “AS IS”, WITHOUT WARRANTY OF ANY KIND, EXPRESS OR IMPLIED,
+// INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A
+// PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT
+// HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION OF
+// CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE
+// OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+
+//! Binary snapshot of `RustBackend`, allowing a run to be stopped and resumed later.
+//!
+//! Layout, all integers and floats little endian:
+//!
+//! - magic `CSSFCKPT` followed by `u32` format version,
+//! - `u8` size of floating point type in bytes (4 or 8), all floats below are stored
+//!   with this precision,
+//! - mode name, subsystem dimensions,
+//! - matrices `initial`, `visibility`, `intermediate`, `visibility_reduced`,
+//! - scalars `aa1`, `aa4`, `aa6`, `dd1`,
+//! - symmetries (`u8` tag, 0 none, 1 sequential, 2 group) and projection (`u8` tag),
+//! - corrections,
+//! - state of backend random generator and of every candidate stream.
+//!
+//! Strings are stored as `u64` length followed by UTF-8 bytes, sequences as `u64`
+//! length followed by elements, matrices as `u64` shape followed by elements in row
+//! major order, each complex number as real and imaginary part. Random generators are
+//! stored as 32 byte seed, `u64` stream and `u128` word position.
+
+use std::mem;
+
+use ndarray as nd;
+use num::Complex;
+use num_traits::Float;
+use rand::SeedableRng;
+
+use super::{
+    BackendRng, CandidateOptimizer, CandidateSampler, CandidateStream, RustBackend,
+    Symmetries,
+};
+use crate::error::{Error, Result};
+use crate::shared::AlgoMode;
+
+const MAGIC: &[u8; 8] = b"CSSFCKPT";
+const VERSION: u32 = 1;
+
+const SYMMETRIES_NONE: u8 = 0;
+const SYMMETRIES_SEQUENTIAL: u8 = 1;
+const SYMMETRIES_GROUP: u8 = 2;
+
+struct Writer<T> {
+    bytes: Vec<u8>,
+    marker: std::marker::PhantomData<T>,
+}
+
+impl<T> Writer<T>
+where
+    T: Float,
+{
+    fn new() -> Self {
+        Writer {
+            bytes: Vec::new(),
+            marker: std::marker::PhantomData,
+        }
+    }
+
+    fn u8(&mut self, value: u8) {
+        self.bytes.push(value);
+    }
+
+    fn u32(&mut self, value: u32) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn u64(&mut self, value: u64) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn usize(&mut self, value: usize) {
+        self.u64(value as u64);
+    }
+
+    fn float(&mut self, value: T) {
+        if mem::size_of::<T>() == 4 {
+            let value = value.to_f32().unwrap();
+            self.bytes.extend_from_slice(&value.to_le_bytes());
+        } else {
+            let value = value.to_f64().unwrap();
+            self.bytes.extend_from_slice(&value.to_le_bytes());
+        }
+    }
+
+    fn string(&mut self, value: &str) {
+        self.usize(value.len());
+        self.bytes.extend_from_slice(value.as_bytes());
+    }
+
+    fn matrix(&mut self, value: &nd::Array2<Complex<T>>) {
+        self.usize(value.dim().0);
+        self.usize(value.dim().1);
+        for element in value.iter() {
+            self.float(element.re);
+            self.float(element.im);
+        }
+    }
+
+    fn matrices(&mut self, value: &[nd::Array2<Complex<T>>]) {
+        self.usize(value.len());
+        for matrix in value {
+            self.matrix(matrix);
+        }
+    }
+
+    fn rng(&mut self, rng: &BackendRng) {
+        self.bytes.extend_from_slice(&rng.get_seed());
+        self.u64(rng.get_stream());
+        self.bytes
+            .extend_from_slice(&rng.get_word_pos().to_le_bytes());
+    }
+}
+
+struct Reader<'a, T> {
+    bytes: &'a [u8],
+    marker: std::marker::PhantomData<T>,
+}
+
+impl<'a, T> Reader<'a, T>
+where
+    T: Float,
+{
+    fn new(bytes: &'a [u8]) -> Self {
+        Reader {
+            bytes,
+            marker: std::marker::PhantomData,
+        }
+    }
+
+    fn take(&mut self, count: usize) -> Result<&'a [u8]> {
+        if count > self.bytes.len() {
+            return Err(Error::Value("Checkpoint is truncated.".to_string()));
+        }
+        let (head, tail) = self.bytes.split_at(count);
+        self.bytes = tail;
+        Ok(head)
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N]> {
+        Ok(self.take(N)?.try_into().unwrap())
+    }
+
+    fn u8(&mut self) -> Result<u8> {
+        Ok(self.array::<1>()?[0])
+    }
+
+    fn u32(&mut self) -> Result<u32> {
+        Ok(u32::from_le_bytes(self.array()?))
+    }
+
+    fn u64(&mut self) -> Result<u64> {
+        Ok(u64::from_le_bytes(self.array()?))
+    }
+
+    fn usize(&mut self) -> Result<usize> {
+        usize::try_from(self.u64()?)
+            .map_err(|_| Error::Value("Checkpoint contains invalid size.".to_string()))
+    }
+
+    /// Read length of sequence of elements occupying at least `element_size` bytes
+    /// each, rejecting lengths which can not fit in remaining bytes before anything is
+    /// allocated.
+    fn length(&mut self, element_size: usize) -> Result<usize> {
+        let length = self.usize()?;
+        match length.checked_mul(element_size) {
+            Some(size) if size <= self.bytes.len() => Ok(length),
+            _ => Err(Error::Value("Checkpoint is truncated.".to_string())),
+        }
+    }
+
+    fn float(&mut self) -> Result<T> {
+        Ok(if mem::size_of::<T>() == 4 {
+            T::from(f32::from_le_bytes(self.array()?)).unwrap()
+        } else {
+            T::from(f64::from_le_bytes(self.array()?)).unwrap()
+        })
+    }
+
+    fn string(&mut self) -> Result<String> {
+        let length = self.length(1)?;
+        String::from_utf8(self.take(length)?.to_vec()).map_err(|_| {
+            Error::Value("Checkpoint contains invalid string.".to_string())
+        })
+    }
+
+    fn matrix(&mut self) -> Result<nd::Array2<Complex<T>>> {
+        let rows = self.usize()?;
+        let columns = self.usize()?;
+        let size = rows
+            .checked_mul(columns)
+            .filter(|size| {
+                size.checked_mul(2 * mem::size_of::<T>())
+                    .map_or(false, |bytes| bytes <= self.bytes.len())
+            })
+            .ok_or_else(|| Error::Value("Checkpoint is truncated.".to_string()))?;
+        let mut elements = Vec::with_capacity(size);
+        for _ in 0..size {
+            let re = self.float()?;
+            let im = self.float()?;
+            elements.push(Complex::new(re, im));
+        }
+        Ok(nd::Array2::from_shape_vec((rows, columns), elements)?)
+    }
+
+    fn matrices(&mut self) -> Result<Vec<nd::Array2<Complex<T>>>> {
+        let length = self.length(16)?;
+        (0..length).map(|_| self.matrix()).collect()
+    }
+
+    fn rng(&mut self) -> Result<BackendRng> {
+        let mut rng = BackendRng::from_seed(self.array()?);
+        rng.set_stream(self.u64()?);
+        rng.set_word_pos(u128::from_le_bytes(self.array()?));
+        Ok(rng)
+    }
+}
+
+impl<T, C, O> RustBackend<T, C, O>
+where
+    T: Float
+        + std::fmt::Debug
+        + rand_distr::uniform::SampleUniform
+        + Send
+        + Sync
+        + 'static,
+    C: CandidateSampler<T>,
+    O: CandidateOptimizer<T, C::Candidate>,
+{
+    /// Serialize complete state of the backend, backend restored with `from_bytes`
+    /// continues exactly as this one would.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut writer = Writer::<T>::new();
+
+        writer.bytes.extend_from_slice(MAGIC);
+        writer.u32(VERSION);
+        writer.u8(mem::size_of::<T>() as u8);
+
+        writer.string(self.mode.name());
+        writer.usize(self.dimensions.len());
+        for dimension in &self.dimensions {
+            writer.usize(*dimension);
+        }
+
+        writer.matrix(&self.initial);
+        writer.matrix(&self.visibility);
+        writer.matrix(&self.intermediate);
+        writer.matrix(&self.visibility_reduced);
+
+        writer.float(self.aa1);
+        writer.float(self.aa4);
+        writer.float(self.aa6);
+        writer.float(self.dd1);
+
+        match &self.symmetries {
+            None => writer.u8(SYMMETRIES_NONE),
+            Some(Symmetries::Sequential(rows)) => {
+                writer.u8(SYMMETRIES_SEQUENTIAL);
+                writer.usize(rows.len());
+                for row in rows {
+                    writer.matrices(row);
+                }
+            }
+            Some(Symmetries::Group(group)) => {
+                writer.u8(SYMMETRIES_GROUP);
+                writer.matrices(group);
+            }
+        }
+        match &self.projection {
+            None => writer.u8(0),
+            Some(projection) => {
+                writer.u8(1);
+                writer.matrix(projection);
+            }
+        }
+
+        writer.usize(self.corrections.len());
+        for (iteration, index, value) in &self.corrections {
+            writer.usize(*iteration);
+            writer.usize(*index);
+            writer.float(*value);
+        }
+
+        writer.rng(&self.rng);
+        writer.usize(self.streams.len());
+        for stream in &self.streams {
+            writer.rng(&stream.rng);
+        }
+
+        writer.bytes
+    }
+
+    /// Restore backend from snapshot created with `to_bytes`. Snapshot has to be
+    /// created by backend with the same floating point precision.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        let mut reader = Reader::<T>::new(bytes);
+
+        if reader.take(MAGIC.len()).ok() != Some(&MAGIC[..]) {
+            return Err(Error::Value(
+                "Data is not a backend checkpoint.".to_string(),
+            ));
+        }
+        let version = reader.u32()?;
+        if version != VERSION {
+            return Err(Error::Value(format!(
+                "Unsupported checkpoint version {}, expected {}.",
+                version, VERSION
+            )));
+        }
+        let precision = reader.u8()? as usize;
+        if precision != mem::size_of::<T>() {
+            return Err(Error::Value(format!(
+                "Checkpoint stores {} byte floats, backend uses {} byte floats.",
+                precision,
+                mem::size_of::<T>()
+            )));
+        }
+
+        let mode_name = reader.string()?;
+        let mode = AlgoMode::from_name(&mode_name).ok_or_else(|| {
+            Error::NotImplemented(format!(
+                "Mode '{}' is not supported by Rust backend.",
+                mode_name
+            ))
+        })?;
+        let dimensions = (0..reader.length(8)?)
+            .map(|_| reader.usize())
+            .collect::<Result<Vec<_>>>()?;
+
+        let initial = reader.matrix()?;
+        // Validates dimensions against the initial state the same way as construction
+        // of new backend does, everything else is replaced by checkpoint contents.
+        let mut backend =
+            Self::with_dimensions(&initial, &dimensions, mode, T::one(), Some(0))?;
+
+        backend.visibility = reader.matrix()?;
+        backend.intermediate = reader.matrix()?;
+        backend.visibility_reduced = reader.matrix()?;
+        for (matrix, name) in [
+            (&backend.visibility, "visibility"),
+            (&backend.intermediate, "intermediate"),
+            (&backend.visibility_reduced, "visibility_reduced"),
+        ] {
+            super::ensure_same_shape(&matrix.view(), &initial.view(), name)?;
+        }
+
+        backend.aa1 = reader.float()?;
+        backend.aa4 = reader.float()?;
+        backend.aa6 = reader.float()?;
+        backend.dd1 = reader.float()?;
+
+        backend.symmetries = match reader.u8()? {
+            SYMMETRIES_NONE => None,
+            SYMMETRIES_SEQUENTIAL => {
+                let rows = (0..reader.length(8)?)
+                    .map(|_| reader.matrices())
+                    .collect::<Result<Vec<_>>>()?;
+                Some(Symmetries::Sequential(rows))
+            }
+            SYMMETRIES_GROUP => Some(Symmetries::Group(reader.matrices()?)),
+            tag => {
+                return Err(Error::Value(format!(
+                    "Checkpoint contains unknown symmetries kind {}.",
+                    tag
+                )))
+            }
+        };
+        if let Some(symmetries) = &backend.symmetries {
+            let matrices: Vec<_> = match symmetries {
+                Symmetries::Sequential(rows) => rows.iter().flatten().collect(),
+                Symmetries::Group(group) => group.iter().collect(),
+            };
+            for matrix in matrices {
+                super::ensure_same_shape(&matrix.view(), &initial.view(), "Symmetry")?;
+            }
+        }
+        backend.projection = match reader.u8()? {
+            0 => None,
+            _ => {
+                let projection = reader.matrix()?;
+                super::ensure_same_shape(
+                    &projection.view(),
+                    &initial.view(),
+                    "Projection",
+                )?;
+                Some(projection)
+            }
+        };
+
+        backend.corrections = (0..reader.length(16 + mem::size_of::<T>())?)
+            .map(|_| Ok((reader.usize()?, reader.usize()?, reader.float()?)))
+            .collect::<Result<Vec<_>>>()?;
+
+        backend.rng = reader.rng()?;
+        backend.streams = (0..reader.length(56)?)
+            .map(|_| {
+                Ok(CandidateStream {
+                    rng: reader.rng()?,
+                    sampler: backend.sampler.clone(),
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        if !reader.bytes.is_empty() {
+            return Err(Error::Value(
+                "Checkpoint contains unexpected trailing data.".to_string(),
+            ));
+        }
+        Ok(backend)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::Backend;
+
+    /// Backend working on 3 qubit GHZ state, so that candidates get accepted.
+    fn backend() -> RustBackend<f64> {
+        let mut vector = nd::Array1::<Complex<f64>>::zeros(8);
+        vector[0] = Complex::new(0.5f64.sqrt(), 0.0);
+        vector[7] = Complex::new(0.5f64.sqrt(), 0.0);
+        let column = vector.view().into_shape((8, 1)).unwrap();
+        let state = column.dot(&column.t());
+
+        let mut backend = <RustBackend<f64> as Backend<f64>>::new(
+            &state.view(),
+            &[2, 2, 2],
+            AlgoMode::FSnQd,
+            0.4,
+            Some(7),
+        )
+        .unwrap();
+        backend.run_epoch(500, 0);
+        assert!(!backend.get_corrections().is_empty());
+        backend
+    }
+
+    fn assert_continues_same(
+        mut original: RustBackend<f64>,
+        mut restored: RustBackend<f64>,
+    ) {
+        assert_eq!(original.get_corrections(), restored.get_corrections());
+        original.run_epoch(500, 1);
+        restored.run_epoch(500, 1);
+        assert_eq!(original.get_corrections(), restored.get_corrections());
+        assert_eq!(original.get_state(), restored.get_state());
+    }
+
+    #[test]
+    fn trait_round_trip() {
+        let original = backend();
+        let bytes = Backend::to_bytes(&original).unwrap();
+        let restored = <RustBackend<f64> as Backend<f64>>::from_bytes(&bytes).unwrap();
+        assert_continues_same(original, restored);
+    }
+
+    #[test]
+    fn trait_checkpoint_file_round_trip() {
+        let original = backend();
+        let path = std::env::temp_dir()
+            .join(format!("cssfinder-checkpoint-{}.bin", std::process::id()));
+        original.save_checkpoint(&path).unwrap();
+        let restored = RustBackend::<f64>::load_checkpoint(&path);
+        std::fs::remove_file(&path).unwrap();
+        assert_continues_same(original, restored.unwrap());
+    }
+
+    #[test]
+    fn precision_mismatch_is_rejected() {
+        let bytes = Backend::to_bytes(&backend()).unwrap();
+        assert!(<RustBackend<f32> as Backend<f32>>::from_bytes(&bytes).is_err());
+    }
+}
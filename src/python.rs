@@ -0,0 +1,856 @@
+// Copyright 2023 Krzysztof Wisniewski <argmaster.world@gmail.com>
+//
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy of this
+// software and associated documentation files (the “Software”), to deal in the Software
+// without restriction, including without limitation the rights to use, copy, modify,
+// merge, publish, distribute, sublicense, and/or sell copies of the Software, and to
+// permit persons to whom the Software is furnished to do so, subject to the following
+// conditions:
+//
+// The above copyright notice and this permission notice shall be included in all copies
+// or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED “AS IS”, WITHOUT WARRANTY OF ANY KIND, EXPRESS OR IMPLIED,
+// INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A
+// PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT
+// HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION OF
+// CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE
+// OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+
+use std::ops::ControlFlow;
+use std::time::{Duration, Instant};
+
+use ndarray as nd;
+use num::Complex;
+use num_traits::Float;
+use pyo3::{prelude::*, types::PyDict};
+
+use crate::backend::{Backend, Progress};
+use crate::error::Error;
+use crate::mtx::MtxFormat;
+use crate::naive;
+
+/// Backend classes exported to cssfinder by `export_backend`, as backend name,
+/// submodule, class name and member of `Precision` enum.
+const EXPORTED_BACKENDS: &[(&str, &str, &str, &str)] = &[
+    ("rust_naive", "complex128", "NaiveRustBackendF64", "DOUBLE"),
+    ("rust_naive", "complex64", "NaiveRustBackendF32", "SINGLE"),
+];
+
+/// A Python module implemented in Rust.
+#[pymodule]
+fn cssfinder_backend_rust(py: Python, m: &PyModule) -> PyResult<()> {
+    complex64::register(py, m)?;
+    complex128::register(py, m)?;
+
+    m.add("__version__", "0.1.0")?;
+
+    #[pyfunction]
+    fn export_backend(py: Python<'_>) -> PyResult<&PyDict> {
+        Python::with_gil(|_py| {
+            let cssfinder_cssfproject = PyModule::import(py, "cssfinder.cssfproject")
+                .map_err(|error| {
+                Error::Import(format!(
+                    "Exporting backends requires cssfinder package: {}",
+                    error
+                ))
+            })?;
+            let precision_enum = cssfinder_cssfproject.getattr("Precision")?;
+
+            let cssfinder_backend_rust_module =
+                PyModule::import(py, "cssfinder_backend_rust")?;
+
+            let backends_dict = PyDict::new(py);
+
+            for &(name, module, class, precision) in EXPORTED_BACKENDS {
+                let backend_class = cssfinder_backend_rust_module
+                    .getattr(module)?
+                    .getattr(class)?;
+
+                backends_dict.set_item(
+                    (name, precision_enum.getattr(precision)?),
+                    backend_class,
+                )?;
+            }
+
+            Ok(backends_dict)
+        })
+    }
+    m.add_function(wrap_pyfunction!(export_backend, m)?)?;
+
+    Ok(())
+}
+
+/// Ensure `idx` refers to one of `quantity` subsystems.
+fn ensure_subsystem(idx: usize, quantity: usize) -> Result<(), Error> {
+    if idx >= quantity {
+        return Err(Error::Value(format!(
+            "Subsystem index {} out of range for {} subsystems.",
+            idx, quantity
+        )));
+    }
+    Ok(())
+}
+
+/// Ensure square matrix of side `size`, which is `None` when computing it overflowed,
+/// fits in memory in double precision. `system` describes the matrix in error message.
+fn ensure_allocatable(
+    size: Option<usize>,
+    system: impl FnOnce() -> String,
+) -> Result<usize, Error> {
+    size.filter(|size| {
+        size.checked_mul(*size)
+            .and_then(|length| length.checked_mul(std::mem::size_of::<Complex<f64>>()))
+            .map_or(false, |bytes| bytes <= isize::MAX as usize)
+    })
+    .ok_or_else(|| Error::Value(format!("{} is too large.", system())))
+}
+
+/// Size of system of `quantity` subsystems of dimension `depth`.
+fn system_size(depth: usize, quantity: usize) -> Result<usize, Error> {
+    let size = u32::try_from(quantity)
+        .ok()
+        .and_then(|quantity| depth.checked_pow(quantity));
+    ensure_allocatable(size, || {
+        format!("System of {} subsystems of dimension {}", quantity, depth)
+    })
+}
+
+/// Size of bipartite system of subsystems of dimensions `depth` and `quantity`.
+fn bipartite_size(depth: usize, quantity: usize) -> Result<usize, Error> {
+    ensure_allocatable(depth.checked_mul(quantity), || {
+        format!(
+            "Bipartite system of subsystems of dimensions {} and {}",
+            depth, quantity
+        )
+    })
+}
+
+/// Ensure `state` is square matrix of size `size`, `name` is used to refer to
+/// `state` in error message.
+fn ensure_state<T>(
+    state: &ndarray::ArrayView2<T>,
+    size: usize,
+    name: &str,
+) -> Result<(), Error> {
+    if naive::ensure_square(state, name)? != size {
+        return Err(Error::Value(format!(
+            "{} of shape {:?} does not match system of size {}.",
+            name,
+            state.dim(),
+            size
+        )));
+    }
+    Ok(())
+}
+
+/// Number of iterations between progress callbacks when not specified explicitly.
+const DEFAULT_PROGRESS_INTERVAL: i64 = 1000;
+
+/// Time between checks for pending signals during epoch, GIL has to be acquired for
+/// every check.
+const SIGNAL_CHECK_PERIOD: Duration = Duration::from_millis(100);
+
+/// Validate progress interval given to `run_epoch`.
+fn progress_interval(interval: Option<i64>) -> Result<i64, Error> {
+    let interval = interval.unwrap_or(DEFAULT_PROGRESS_INTERVAL);
+    if interval <= 0 {
+        return Err(Error::Value(format!(
+            "Progress interval has to be positive, got {}.",
+            interval
+        )));
+    }
+    Ok(interval)
+}
+
+/// Resolve Matrix Market format given to `save_mtx`, array unless specified.
+fn mtx_format(format: Option<&str>) -> Result<MtxFormat, Error> {
+    match format {
+        None => Ok(MtxFormat::Array),
+        Some(name) => MtxFormat::from_name(name).ok_or_else(|| {
+            Error::Value(format!(
+                "Matrix Market format has to be \"array\" or \"coordinate\", got \"{}\".",
+                name
+            ))
+        }),
+    }
+}
+
+/// Run epoch of `backend` with GIL released, calling progress `callback` every
+/// `interval` iterations and handling pending signals every `SIGNAL_CHECK_PERIOD`.
+/// Exception raised by callback or signal handler (eg. `KeyboardInterrupt`) stops the
+/// epoch between iterations, so backend stays in consistent state.
+fn run_epoch<T, B>(
+    py: Python<'_>,
+    backend: &mut B,
+    iterations: i64,
+    epoch_index: usize,
+    callback: Option<PyObject>,
+    interval: Option<i64>,
+) -> PyResult<()>
+where
+    T: IntoPy<PyObject>,
+    B: Backend<T> + Send,
+{
+    let interval = progress_interval(interval)?;
+    let outcome = py.allow_threads(|| {
+        let mut last_signal_check = Instant::now();
+        backend.run_epoch_with(iterations, epoch_index, 1, |progress| {
+            if let Some(callback) = &callback {
+                if progress.iteration % interval == 0 {
+                    call_progress(callback, progress)?;
+                }
+            }
+            if last_signal_check.elapsed() >= SIGNAL_CHECK_PERIOD {
+                last_signal_check = Instant::now();
+                if let Err(error) = Python::with_gil(|py| py.check_signals()) {
+                    return ControlFlow::Break(Some(error));
+                }
+            }
+            ControlFlow::Continue(())
+        })
+    });
+    match outcome {
+        ControlFlow::Break(Some(error)) => Err(error),
+        _ => Ok(()),
+    }
+}
+
+/// Call Python progress `callback`, breaking when it returns true value or raises,
+/// in the latter case exception is carried in break value.
+fn call_progress<T>(
+    callback: &PyObject,
+    progress: Progress<T>,
+) -> ControlFlow<Option<PyErr>>
+where
+    T: IntoPy<PyObject>,
+{
+    Python::with_gil(|py| {
+        let arguments = (progress.iteration, progress.corrections, progress.distance);
+        match callback
+            .call1(py, arguments)
+            .and_then(|result| result.is_true(py))
+        {
+            Ok(false) => ControlFlow::Continue(()),
+            Ok(true) => ControlFlow::Break(None),
+            Err(error) => ControlFlow::Break(Some(error)),
+        }
+    })
+}
+
+/// Floating point type of backend, arrays are received from Python in double
+/// precision and converted to it.
+trait Precision: Float {
+    /// Convert `array` to this precision, borrowing it when no conversion is needed.
+    fn from_f64(
+        array: nd::ArrayView2<Complex<f64>>,
+    ) -> nd::CowArray<Complex<Self>, nd::Ix2>;
+}
+
+impl Precision for f64 {
+    fn from_f64(
+        array: nd::ArrayView2<Complex<f64>>,
+    ) -> nd::CowArray<Complex<Self>, nd::Ix2> {
+        array.into()
+    }
+}
+
+impl Precision for f32 {
+    fn from_f64(
+        array: nd::ArrayView2<Complex<f64>>,
+    ) -> nd::CowArray<Complex<Self>, nd::Ix2> {
+        array
+            .mapv(|x| Complex::new(x.re as f32, x.im as f32))
+            .into()
+    }
+}
+
+/// Define Python class `$class` exposing backend `$backend`, which has to implement
+/// `Backend<$float>` and `Clone`. Arrays are accepted in double precision and converted
+/// to `$float` with `Precision`, `$module` is qualified name of submodule class is
+/// registered in.
+macro_rules! backend_class {
+    ($class:ident, $backend:ty, $float:ty, $module:tt) => {
+        use crate::backend::Backend as _;
+
+        #[pyo3::pyclass(module = $module)]
+        pub struct $class {
+            backend: $backend,
+        }
+
+        #[pyo3::pymethods]
+        impl $class {
+            #[new]
+            #[allow(clippy::too_many_arguments)]
+            fn new(
+                initial: numpy::PyReadonlyArray2<num::Complex<f64>>,
+                depth: usize,
+                quantity: usize,
+                mode: crate::shared::AlgoMode,
+                visibility: $float,
+                is_debug: Option<bool>,
+                dimensions: Option<Vec<usize>>,
+                seed: Option<u64>,
+            ) -> pyo3::PyResult<Self> {
+                let state_array =
+                    <$float as crate::python::Precision>::from_f64(initial.as_array());
+                assert!(is_debug.unwrap_or(false) || !is_debug.unwrap_or(false));
+
+                let dimensions =
+                    dimensions.unwrap_or_else(|| mode.dimensions(depth, quantity));
+                let backend = <$backend as crate::backend::Backend<$float>>::new(
+                    &state_array.view(),
+                    &dimensions,
+                    mode,
+                    visibility,
+                    seed,
+                )?;
+
+                Ok($class { backend })
+            }
+
+            fn set_symmetries(
+                &mut self,
+                symmetries: Vec<Vec<numpy::PyReadonlyArray2<num::Complex<f64>>>>,
+                mode: Option<crate::shared::SymmetryMode>,
+            ) -> pyo3::PyResult<()> {
+                let symmetries_local = symmetries
+                    .into_iter()
+                    .map(|inner_vec| {
+                        inner_vec
+                            .into_iter()
+                            .map(|pyarray| {
+                                <$float as crate::python::Precision>::from_f64(
+                                    pyarray.as_array(),
+                                )
+                                .into_owned()
+                            })
+                            .collect()
+                    })
+                    .collect();
+                self.backend
+                    .set_symmetries(
+                        symmetries_local,
+                        mode.unwrap_or(crate::shared::SymmetryMode::Sequential),
+                    )
+                    .map_err(pyo3::PyErr::from)
+            }
+
+            fn set_projection(
+                &mut self,
+                projection: numpy::PyReadonlyArray2<num::Complex<f64>>,
+            ) -> pyo3::PyResult<()> {
+                self.backend
+                    .set_projection(
+                        <$float as crate::python::Precision>::from_f64(
+                            projection.as_array(),
+                        )
+                        .into_owned(),
+                    )
+                    .map_err(pyo3::PyErr::from)
+            }
+
+            fn get_state<'py>(
+                &self,
+                py: pyo3::Python<'py>,
+            ) -> pyo3::PyResult<&'py numpy::PyArray2<num::Complex<$float>>> {
+                let array_out = self.backend.get_state();
+                Ok(numpy::PyArray::from_owned_array(py, array_out.to_owned()))
+            }
+
+            fn get_corrections(&self) -> pyo3::PyResult<Vec<(usize, usize, $float)>> {
+                Ok(self.backend.get_corrections().to_owned())
+            }
+
+            fn set_threads(&mut self, threads: usize) -> pyo3::PyResult<()> {
+                self.backend.set_threads(threads).map_err(pyo3::PyErr::from)
+            }
+
+            fn get_threads(&self) -> pyo3::PyResult<usize> {
+                Ok(self.backend.get_threads())
+            }
+
+            fn get_dimensions(&self) -> pyo3::PyResult<Vec<usize>> {
+                Ok(self.backend.get_dimensions().to_vec())
+            }
+
+            fn get_corrections_count(&self) -> pyo3::PyResult<usize> {
+                Ok(self.backend.get_corrections().len())
+            }
+
+            /// Serialize complete state of the backend, see `from_bytes`.
+            fn to_bytes<'py>(
+                &self,
+                py: pyo3::Python<'py>,
+            ) -> pyo3::PyResult<&'py pyo3::types::PyBytes> {
+                let data = crate::backend::Backend::to_bytes(&self.backend)?;
+                Ok(pyo3::types::PyBytes::new(py, &data))
+            }
+
+            /// Create backend from bytes returned by `to_bytes`.
+            #[classmethod]
+            fn from_bytes(
+                _cls: &pyo3::types::PyType,
+                data: &[u8],
+            ) -> pyo3::PyResult<Self> {
+                let backend =
+                    <$backend as crate::backend::Backend<$float>>::from_bytes(data)?;
+                Ok($class { backend })
+            }
+
+            fn __reduce__<'py>(
+                slf: &'py pyo3::PyCell<Self>,
+                py: pyo3::Python<'py>,
+            ) -> pyo3::PyResult<(&'py pyo3::PyAny, (&'py pyo3::types::PyBytes,))> {
+                let from_bytes = slf.get_type().getattr("from_bytes")?;
+                let data = slf.borrow().to_bytes(py)?;
+                Ok((from_bytes, (data,)))
+            }
+
+            fn __deepcopy__(&self, _memo: &pyo3::PyAny) -> Self {
+                $class {
+                    backend: self.backend.clone(),
+                }
+            }
+
+            /// Write complete state of the backend to file at `path`, run can be
+            /// resumed from it with `load_checkpoint`.
+            fn save_checkpoint(&self, path: std::path::PathBuf) -> pyo3::PyResult<()> {
+                self.backend
+                    .save_checkpoint(path)
+                    .map_err(pyo3::PyErr::from)
+            }
+
+            /// Create backend from file written with `save_checkpoint`.
+            #[staticmethod]
+            fn load_checkpoint(path: std::path::PathBuf) -> pyo3::PyResult<Self> {
+                let backend =
+                    <$backend as crate::backend::Backend<$float>>::load_checkpoint(
+                        path,
+                    )?;
+                Ok($class { backend })
+            }
+
+            /// Run epoch of `iterations` iterations. When `callback` is given it is
+            /// called every `interval` iterations with number of completed iterations,
+            /// number of corrections and value of the latest correction (or None),
+            /// returning true value from it stops the epoch. Epoch is also stopped when
+            /// signal handler raises, eg. on Ctrl-C.
+            fn run_epoch(
+                &mut self,
+                py: pyo3::Python<'_>,
+                iterations: i64,
+                epoch_index: usize,
+                callback: Option<pyo3::PyObject>,
+                interval: Option<i64>,
+            ) -> pyo3::PyResult<()> {
+                crate::python::run_epoch(
+                    py,
+                    &mut self.backend,
+                    iterations,
+                    epoch_index,
+                    callback,
+                    interval,
+                )
+            }
+        }
+    };
+}
+
+/// Define submodule `$module` with kernels and backend class `$class` operating on
+/// `Complex<$float>` arrays, together with its `register` function. `$qualname` is
+/// qualified name of submodule, as seen from Python.
+macro_rules! kernel_module {
+    ($module:ident, $float:ty, $class:ident, $qualname:tt) => {
+        mod $module {
+            use num::Complex;
+            use numpy as np;
+            use pyo3::prelude::*;
+
+            use super::{
+                bipartite_size, ensure_allocatable, ensure_state, ensure_subsystem,
+                system_size,
+            };
+            use crate::error::Error;
+            use crate::naive::{ensure_same_shape, ensure_square};
+
+            #[pyfunction]
+            pub fn product(
+                _py: Python,
+                a: np::PyReadonlyArray2<Complex<$float>>,
+                b: np::PyReadonlyArray2<Complex<$float>>,
+            ) -> PyResult<$float> {
+                let array_1 = a.as_array();
+                let array_2 = b.as_array();
+                ensure_same_shape(&array_1, &array_2, "a")?;
+                Ok(super::naive::product(&array_1, &array_2))
+            }
+
+            #[pyfunction]
+            pub fn normalize<'py>(
+                py: Python<'py>,
+                a: np::PyReadonlyArray1<Complex<$float>>,
+            ) -> PyResult<&'py np::PyArray1<Complex<$float>>> {
+                let array_1 = a.as_array();
+                let array_2 = super::naive::normalize(&array_1);
+                let array_out = np::PyArray::from_owned_array(py, array_2);
+                Ok(array_out)
+            }
+
+            #[pyfunction]
+            pub fn project<'py>(
+                py: Python<'py>,
+                a: np::PyReadonlyArray1<Complex<$float>>,
+            ) -> PyResult<&'py np::PyArray2<Complex<$float>>> {
+                let array_1 = a.as_array();
+                let array_2 = super::naive::project(&array_1);
+                let array_out = np::PyArray::from_owned_array(py, array_2);
+                Ok(array_out)
+            }
+
+            #[pyfunction]
+            pub fn kronecker<'py>(
+                py: Python<'py>,
+                a: np::PyReadonlyArray2<Complex<$float>>,
+                b: np::PyReadonlyArray2<Complex<$float>>,
+            ) -> PyResult<&'py np::PyArray2<Complex<$float>>> {
+                let array_1 = a.as_array();
+                let array_2 = b.as_array();
+                ensure_square(&array_1, "a")?;
+                ensure_square(&array_2, "b")?;
+                let array_3 = super::naive::kronecker(&array_1, &array_2);
+                let array_out = np::PyArray::from_owned_array(py, array_3);
+                Ok(array_out)
+            }
+
+            #[pyfunction]
+            pub fn rotate<'py>(
+                py: Python<'py>,
+                a: np::PyReadonlyArray2<Complex<$float>>,
+                b: np::PyReadonlyArray2<Complex<$float>>,
+            ) -> PyResult<&'py np::PyArray2<Complex<$float>>> {
+                ensure_same_shape(&b.as_array(), &a.as_array(), "b")?;
+                let array_3 = super::naive::rotate(&a.as_array(), &b.as_array());
+                let array_out = np::PyArray::from_owned_array(py, array_3);
+                Ok(array_out)
+            }
+
+            #[pyfunction]
+            pub fn get_random_haar_1d(
+                py: Python<'_>,
+                a: usize,
+                seed: Option<u64>,
+            ) -> PyResult<&np::PyArray1<Complex<$float>>> {
+                let mut rng = super::naive::rng_from_seed(seed);
+                let array_3 = super::naive::get_random_haar_1d(a, &mut rng);
+                let array_out = np::PyArray::from_owned_array(py, array_3);
+                Ok(array_out)
+            }
+
+            #[pyfunction]
+            pub fn expand_d_fs<'py>(
+                py: Python<'py>,
+                value: np::PyReadonlyArray2<Complex<$float>>,
+                depth: usize,
+                quantity: usize,
+                idx: usize,
+            ) -> PyResult<&'py np::PyArray2<Complex<$float>>> {
+                let size = ensure_square(&value.as_array(), "value")?;
+                ensure_subsystem(idx, quantity)?;
+                let others = u32::try_from(quantity - 1)
+                    .ok()
+                    .and_then(|others| depth.checked_pow(others));
+                ensure_allocatable(others.and_then(|others| others.checked_mul(size)), || {
+                    format!(
+                        "Expansion of value of size {} to {} subsystems of dimension {}",
+                        size, quantity, depth
+                    )
+                })?;
+                let array_3 = super::naive::expand_d_fs(
+                    &value.as_array(),
+                    &vec![depth; quantity],
+                    idx,
+                );
+                let array_out = np::PyArray::from_owned_array(py, array_3);
+                Ok(array_out)
+            }
+
+            #[pyfunction]
+            pub fn rotate_d_fs<'py>(
+                py: Python<'py>,
+                rho: np::PyReadonlyArray2<Complex<$float>>,
+                value: np::PyReadonlyArray2<Complex<$float>>,
+                depth: usize,
+                quantity: usize,
+                idx: usize,
+            ) -> PyResult<&'py np::PyArray2<Complex<$float>>> {
+                ensure_state(&rho.as_array(), system_size(depth, quantity)?, "rho")?;
+                if ensure_square(&value.as_array(), "value")? != depth {
+                    return Err(Error::Value(format!(
+                        "value of shape {:?} does not match depth {}.",
+                        value.shape(),
+                        depth
+                    ))
+                    .into());
+                }
+                ensure_subsystem(idx, quantity)?;
+                let array_3 = super::naive::rotate_d_fs(
+                    &rho.as_array(),
+                    &value.as_array(),
+                    &vec![depth; quantity],
+                    idx,
+                );
+                let array_out = np::PyArray::from_owned_array(py, array_3);
+                Ok(array_out)
+            }
+
+            #[pyfunction]
+            pub fn random_unitary_d_fs(
+                py: Python<'_>,
+                depth: usize,
+                quantity: usize,
+                idx: usize,
+                seed: Option<u64>,
+            ) -> PyResult<&np::PyArray2<Complex<$float>>> {
+                ensure_subsystem(idx, quantity)?;
+                system_size(depth, quantity)?;
+                let mut rng = super::naive::rng_from_seed(seed);
+                let array_3 = super::naive::random_unitary_d_fs(
+                    &vec![depth; quantity],
+                    idx,
+                    &mut rng,
+                );
+                let array_out = np::PyArray::from_owned_array(py, array_3);
+                Ok(array_out)
+            }
+
+            #[pyfunction]
+            pub fn random_d_fs(
+                py: Python<'_>,
+                depth: usize,
+                quantity: usize,
+                seed: Option<u64>,
+            ) -> PyResult<&np::PyArray2<Complex<$float>>> {
+                ensure_subsystem(0, quantity)?;
+                system_size(depth, quantity)?;
+                let mut rng = super::naive::rng_from_seed(seed);
+                let array_3 =
+                    super::naive::random_d_fs(&vec![depth; quantity], &mut rng);
+                let array_out = np::PyArray::from_owned_array(py, array_3);
+                Ok(array_out)
+            }
+
+            #[pyfunction]
+            pub fn optimize_d_fs<'py>(
+                py: Python<'py>,
+                new_state: np::PyReadonlyArray2<Complex<$float>>,
+                visibility_state: np::PyReadonlyArray2<Complex<$float>>,
+                depth: usize,
+                quantity: usize,
+                updates_count: usize,
+                seed: Option<u64>,
+            ) -> PyResult<&'py np::PyArray2<Complex<$float>>> {
+                ensure_subsystem(0, quantity)?;
+                ensure_state(
+                    &new_state.as_array(),
+                    system_size(depth, quantity)?,
+                    "new_state",
+                )?;
+                ensure_same_shape(
+                    &visibility_state.as_array(),
+                    &new_state.as_array(),
+                    "visibility_state",
+                )?;
+                let mut rng = super::naive::rng_from_seed(seed);
+                let array_out = super::naive::optimize_d_fs(
+                    &new_state.as_array(),
+                    &visibility_state.as_array(),
+                    &vec![depth; quantity],
+                    updates_count,
+                    &mut rng,
+                );
+                let array_out_py = np::PyArray::from_owned_array(py, array_out);
+                Ok(array_out_py)
+            }
+
+            #[pyfunction]
+            pub fn random_bs(
+                py: Python<'_>,
+                depth: usize,
+                quantity: usize,
+                seed: Option<u64>,
+            ) -> PyResult<&np::PyArray2<Complex<$float>>> {
+                bipartite_size(depth, quantity)?;
+                let mut rng = super::naive::rng_from_seed(seed);
+                let array_3 = super::naive::random_bs(depth, quantity, &mut rng);
+                let array_out = np::PyArray::from_owned_array(py, array_3);
+                Ok(array_out)
+            }
+
+            #[pyfunction]
+            pub fn random_unitary_bs(
+                py: Python<'_>,
+                depth: usize,
+                quantity: usize,
+                seed: Option<u64>,
+            ) -> PyResult<&np::PyArray2<Complex<$float>>> {
+                bipartite_size(depth, quantity)?;
+                let mut rng = super::naive::rng_from_seed(seed);
+                let array_3 =
+                    super::naive::random_unitary_bs(depth, quantity, &mut rng);
+                let array_out = np::PyArray::from_owned_array(py, array_3);
+                Ok(array_out)
+            }
+
+            #[pyfunction]
+            pub fn random_unitary_bs_reverse(
+                py: Python<'_>,
+                depth: usize,
+                quantity: usize,
+                seed: Option<u64>,
+            ) -> PyResult<&np::PyArray2<Complex<$float>>> {
+                bipartite_size(depth, quantity)?;
+                let mut rng = super::naive::rng_from_seed(seed);
+                let array_3 =
+                    super::naive::random_unitary_bs_reverse(depth, quantity, &mut rng);
+                let array_out = np::PyArray::from_owned_array(py, array_3);
+                Ok(array_out)
+            }
+
+            #[pyfunction]
+            pub fn optimize_bs<'py>(
+                py: Python<'py>,
+                new_state: np::PyReadonlyArray2<Complex<$float>>,
+                visibility_state: np::PyReadonlyArray2<Complex<$float>>,
+                depth: usize,
+                quantity: usize,
+                updates_count: usize,
+                seed: Option<u64>,
+            ) -> PyResult<&'py np::PyArray2<Complex<$float>>> {
+                ensure_state(
+                    &new_state.as_array(),
+                    bipartite_size(depth, quantity)?,
+                    "new_state",
+                )?;
+                ensure_same_shape(
+                    &visibility_state.as_array(),
+                    &new_state.as_array(),
+                    "visibility_state",
+                )?;
+                let mut rng = super::naive::rng_from_seed(seed);
+                let array_out = super::naive::optimize_bs(
+                    &new_state.as_array(),
+                    &visibility_state.as_array(),
+                    depth,
+                    quantity,
+                    updates_count,
+                    &mut rng,
+                );
+                let array_out_py = np::PyArray::from_owned_array(py, array_out);
+                Ok(array_out_py)
+            }
+
+            #[pyfunction]
+            pub fn noop(_py: Python) -> PyResult<()> {
+                use ndarray as nd;
+                let a = nd::array!([1, 2, 3]);
+                let b = nd::array!([3, 2, 1]);
+
+                let a_len = a.len();
+                let b_len = b.len();
+
+                let a1 = a.into_shape((a_len, 1)).map_err(Error::from)?;
+                let b1 = b.into_shape((1, b_len)).map_err(Error::from)?;
+
+                let c = b1.dot(&a1).into_shape(a_len * b_len);
+
+                println!("{:?}", c);
+
+                Ok(())
+            }
+
+            /// Read square complex matrix from Matrix Market file.
+            #[pyfunction]
+            pub fn load_mtx<'py>(
+                py: Python<'py>,
+                path: std::path::PathBuf,
+            ) -> PyResult<&'py np::PyArray2<Complex<$float>>> {
+                let array_out = crate::mtx::load_mtx::<$float, _>(path)?;
+                Ok(np::PyArray::from_owned_array(py, array_out))
+            }
+
+            /// Write square complex matrix to Matrix Market file, in `format` "array"
+            /// (default) or "coordinate".
+            #[pyfunction]
+            pub fn save_mtx(
+                _py: Python,
+                path: std::path::PathBuf,
+                a: np::PyReadonlyArray2<Complex<$float>>,
+                format: Option<&str>,
+            ) -> PyResult<()> {
+                let format = super::mtx_format(format)?;
+                crate::mtx::save_mtx(path, &a.as_array(), format)?;
+                Ok(())
+            }
+
+            backend_class!(
+                $class,
+                crate::naive::RustBackend<$float>,
+                $float,
+                $qualname
+            );
+
+            /// Create submodule with kernels and backend class and add it to `parent`.
+            pub fn register(py: Python, parent: &PyModule) -> PyResult<()> {
+                let module = PyModule::new(py, stringify!($module))?;
+
+                module.add_function(wrap_pyfunction!(product, parent)?)?;
+                module.add_function(wrap_pyfunction!(normalize, parent)?)?;
+                module.add_function(wrap_pyfunction!(project, parent)?)?;
+                module.add_function(wrap_pyfunction!(kronecker, parent)?)?;
+                module.add_function(wrap_pyfunction!(rotate, parent)?)?;
+                module.add_function(wrap_pyfunction!(get_random_haar_1d, parent)?)?;
+                module.add_function(wrap_pyfunction!(expand_d_fs, parent)?)?;
+                module.add_function(wrap_pyfunction!(rotate_d_fs, parent)?)?;
+                module.add_function(wrap_pyfunction!(random_unitary_d_fs, parent)?)?;
+                module.add_function(wrap_pyfunction!(random_d_fs, parent)?)?;
+                module.add_function(wrap_pyfunction!(optimize_d_fs, parent)?)?;
+                module.add_function(wrap_pyfunction!(random_bs, parent)?)?;
+                module.add_function(wrap_pyfunction!(random_unitary_bs, parent)?)?;
+                module.add_function(wrap_pyfunction!(
+                    random_unitary_bs_reverse,
+                    parent
+                )?)?;
+                module.add_function(wrap_pyfunction!(optimize_bs, parent)?)?;
+                module.add_function(wrap_pyfunction!(load_mtx, parent)?)?;
+                module.add_function(wrap_pyfunction!(save_mtx, parent)?)?;
+                module.add_function(wrap_pyfunction!(noop, parent)?)?;
+
+                module.add_class::<$class>()?;
+
+                parent.add_submodule(module)?;
+                // Submodule is not importable on its own, without entry in
+                // `sys.modules` pickle is unable to find classes defined in it.
+                py.import("sys")?
+                    .getattr("modules")?
+                    .set_item($qualname, module)?;
+
+                Ok(())
+            }
+        }
+    };
+}
+
+kernel_module!(
+    complex128,
+    f64,
+    NaiveRustBackendF64,
+    "cssfinder_backend_rust.complex128"
+);
+kernel_module!(
+    complex64,
+    f32,
+    NaiveRustBackendF32,
+    "cssfinder_backend_rust.complex64"
+);
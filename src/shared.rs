@@ -18,26 +18,103 @@
 // CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE
 // OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
 
+#[cfg(feature = "python")]
 use pyo3::{FromPyObject, PyAny, PyResult};
 
-#[derive(Clone)]
+#[cfg(feature = "python")]
+use crate::error::Error;
+
+/// Kind of entanglement backend searches for, determines shape of candidate states.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum AlgoMode {
+    /// Full separability of n quDits.
     FSnQd,
+    /// Separability of bipartite system with subsystems of different dimensions.
     SBiPa,
+    /// Genuine entanglement of 3 parties.
     G3PaE3qD,
+    /// Genuine entanglement of 4 parties.
     G4PaE3qD,
 }
 
+impl AlgoMode {
+    /// Name of the mode, the same as name of corresponding Python enum member.
+    pub fn name(&self) -> &'static str {
+        match self {
+            AlgoMode::FSnQd => "FSnQd",
+            AlgoMode::SBiPa => "SBiPa",
+            AlgoMode::G3PaE3qD => "G3PaE3qD",
+            AlgoMode::G4PaE3qD => "G4PaE3qD",
+        }
+    }
+
+    /// Dimensions of subsystems for system of `quantity` subsystems of dimension
+    /// `depth` each. In SBiPa mode `depth` and `quantity` are instead dimensions of the
+    /// two subsystems.
+    pub fn dimensions(&self, depth: usize, quantity: usize) -> Vec<usize> {
+        match self {
+            AlgoMode::SBiPa => vec![depth, quantity],
+            _ => vec![depth; quantity],
+        }
+    }
+
+    /// Inverse of `name()`, `None` when name does not refer to any supported mode.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "FSnQd" => Some(AlgoMode::FSnQd),
+            "SBiPa" => Some(AlgoMode::SBiPa),
+            "G3PaE3qD" => Some(AlgoMode::G3PaE3qD),
+            "G4PaE3qD" => Some(AlgoMode::G4PaE3qD),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(feature = "python")]
 impl FromPyObject<'_> for AlgoMode {
     fn extract(ob: &PyAny) -> PyResult<Self> {
-        let variant_name = ob.getattr("name")?.extract::<String>()?;
+        let variant_name = ob
+            .getattr("name")
+            .and_then(|name| name.extract::<String>())
+            .map_err(|_| {
+                Error::Type(format!("Expected AlgoMode member, got {}.", ob))
+            })?;
+
+        AlgoMode::from_name(&variant_name).ok_or_else(|| {
+            Error::NotImplemented(format!(
+                "Mode '{}' is not supported by Rust backend.",
+                variant_name
+            ))
+            .into()
+        })
+    }
+}
+
+/// Interpretation of symmetries passed to backend.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SymmetryMode {
+    /// Symmetries are applied to the state one after another, accumulating results.
+    Sequential,
+    /// Symmetries generate finite group, state is averaged over all its elements.
+    Generators,
+    /// Symmetries are all elements of finite group, state is averaged over them.
+    Group,
+}
+
+#[cfg(feature = "python")]
+impl FromPyObject<'_> for SymmetryMode {
+    fn extract(ob: &PyAny) -> PyResult<Self> {
+        let variant_name = ob.extract::<String>()?;
 
         match variant_name.as_str() {
-            "FSnQd" => Ok(AlgoMode::FSnQd),
-            "SBiPa" => Ok(AlgoMode::SBiPa),
-            "G3PaE3qD" => Ok(AlgoMode::G3PaE3qD),
-            "G4PaE3qD" => Ok(AlgoMode::G4PaE3qD),
-            _ => panic!("Unknown value."),
+            "sequential" => Ok(SymmetryMode::Sequential),
+            "generators" => Ok(SymmetryMode::Generators),
+            "group" => Ok(SymmetryMode::Group),
+            _ => Err(Error::Value(format!(
+                "Unknown symmetry mode '{}'.",
+                variant_name
+            ))
+            .into()),
         }
     }
 }
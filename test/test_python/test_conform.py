@@ -1,10 +1,19 @@
-from typing import ClassVar
-
+import copy
+import pickle
+import signal
+import threading
+from concurrent.futures import ThreadPoolExecutor
+from pathlib import Path
+from typing import Callable, ClassVar, Type
+
+import cssfinder_backend_numpy
+import cssfinder_backend_numpy.numpy._complex64 as numpy_c64
 import cssfinder_backend_numpy.numpy._complex128 as numpy_c128
 import cssfinder_backend_rust as rust_backend
 import numpy as np
+import pytest
 from cssfinder.algorithm.backend.base import BackendBase
-from cssfinder.cssfproject import AlgoMode
+from cssfinder.cssfproject import AlgoMode, Precision
 from cssfinder_backend_numpy.impl import Implementation
 from matplotlib import pyplot as plt
 
@@ -41,7 +50,7 @@ class ValidateConformance:
             np.random.random((32, 32)) + 1j * np.random.random((32, 32))
         ).astype(self.dtype)
 
-        self.limit = 1e-6
+        self.limit = 1e-6 if self.dtype == np.complex128 else 1e-4
 
     def test_product(self) -> None:
         """Validate product return value."""
@@ -57,6 +66,11 @@ class ValidateConformance:
 
         assert np.abs(a - b) < self.limit
 
+    def test_product_shape_mismatch(self) -> None:
+        """Validate product rejects matrices of different shapes."""
+        with pytest.raises(ValueError):
+            self.this.product(self.lhs_mtx, self.mtx32)
+
     def test_normalize(self) -> None:
         """Validate normalize return value."""
 
@@ -110,6 +124,30 @@ class ValidateConformance:
         assert a.shape == b.shape
         assert (np.abs(a - b) < self.limit).conj().all()
 
+    def test_non_contiguous_input(self) -> None:
+        """Validate kernels reading Fortran ordered and strided arrays in place."""
+
+        fortran = np.asfortranarray(self.mtx32)
+        strided = self.mtx32[::2, ::2]
+        unitary = self.this.random_unitary_d_fs(2, 1, 0)
+
+        assert fortran.flags.f_contiguous
+        assert not strided.flags.c_contiguous
+
+        reference = self.reference.rotate(self.lhs_mtx, self.rhs_mtx)
+        this = self.this.rotate(
+            np.asfortranarray(self.lhs_mtx), np.asfortranarray(self.rhs_mtx)
+        )
+        assert (np.abs(reference - this) < self.limit).all()
+
+        reference = self.reference.product(strided, strided.T)
+        this = self.this.product(strided, strided.T)
+        assert np.isclose(reference, this, rtol=self.limit)
+
+        reference = self.this.rotate_d_fs(self.mtx32, unitary, 2, 5, 3)
+        this = self.this.rotate_d_fs(fortran, np.asfortranarray(unitary), 2, 5, 3)
+        assert (np.abs(reference - this) < self.limit).all()
+
     def test_get_random_haar_1d(self) -> None:
         """Validate vector sampling."""
 
@@ -171,6 +209,76 @@ class ValidateConformance:
 
         assert reference_mean.round(2) == this_mean.round(2)
 
+    def test_random_unitary_d_fs_is_unitary(self) -> None:
+        """Validate that sampled local unitaries are unitary."""
+
+        this = self.this.random_unitary_d_fs(5, 1, 0)
+
+        assert (np.abs(this @ this.conj().T - np.identity(5)) < self.limit).all()
+
+    def test_random_unitary_d_fs_conforms(self) -> None:
+        """Validate local unitary differs from identity by rank one term of the same
+        norm as in reference implementation."""
+
+        reference = self.reference.random_unitary_d_fs(5, 1, 0) - np.identity(5)
+        this = self.this.random_unitary_d_fs(5, 1, 0) - np.identity(5)
+
+        assert np.isclose(
+            np.linalg.norm(this), np.linalg.norm(reference), atol=self.limit
+        )
+        assert np.linalg.matrix_rank(this, tol=self.limit) == 1
+        assert np.linalg.matrix_rank(reference, tol=self.limit) == 1
+
+    def test_rotate_d_fs(self) -> None:
+        """Validate local rotation against rotation with expanded unitary."""
+
+        unitary = self.this.random_unitary_d_fs(2, 1, 0)
+
+        for idx in range(5):
+            expanded = self.this.expand_d_fs(unitary, 2, 5, idx)
+            reference = self.this.rotate(self.mtx32, expanded)
+            this = self.this.rotate_d_fs(self.mtx32, unitary, 2, 5, idx)
+
+            assert reference.shape == this.shape
+            assert (np.abs(reference - this) < self.limit).all()
+
+    def test_rotate_d_fs_system_too_large(self) -> None:
+        """Validate size of system overflowing integer is rejected."""
+
+        unitary = self.this.random_unitary_d_fs(2, 1, 0)
+        with pytest.raises(ValueError):
+            self.this.rotate_d_fs(self.mtx32, unitary, 2, 64, 0)
+
+    @pytest.mark.parametrize(
+        ("name", "args"),
+        [
+            ("random_d_fs", (2, 64)),
+            ("random_d_fs", (2, 40)),
+            ("random_unitary_d_fs", (2, 64, 0)),
+            ("random_bs", (2**40, 2**40)),
+            ("random_unitary_bs", (2**31, 2**31)),
+            ("random_unitary_bs_reverse", (2**62, 4)),
+        ],
+    )
+    def test_random_system_too_large(self, name: str, args: tuple) -> None:
+        """Validate system too large to be allocated is rejected."""
+
+        with pytest.raises(ValueError):
+            getattr(self.this, name)(*args)
+
+    def test_expand_d_fs_system_too_large(self) -> None:
+        """Validate expansion too large to be allocated is rejected."""
+
+        unitary = self.this.random_unitary_d_fs(2, 1, 0)
+        with pytest.raises(ValueError):
+            self.this.expand_d_fs(unitary, 2, 64, 0)
+
+    def test_optimize_bs_system_too_large(self) -> None:
+        """Validate bipartite system overflowing integer is rejected."""
+
+        with pytest.raises(ValueError):
+            self.this.optimize_bs(self.mtx32, self.mtx32, 2**32, 2**32, 10)
+
     def test_random_d_fs(self) -> None:
         reference = self.reference.random_d_fs(5, 2)
         this = self.this.random_d_fs(5, 2)
@@ -203,6 +311,66 @@ class ValidateConformance:
 
         assert reference_mean.round(2) == this_mean.round(2)
 
+    def test_random_zero_depth(self) -> None:
+        """Validate zero dimensional subsystems give empty state."""
+
+        assert self.this.random_d_fs(0, 2).shape == (0, 0)
+        assert self.this.random_bs(0, 2).shape == (0, 0)
+
+    @pytest.mark.parametrize("format", ["array", "coordinate"])
+    def test_mtx_roundtrip(self, tmp_path: Path, format: str) -> None:
+        """Validate matrix saved to Matrix Market file is loaded unchanged."""
+
+        self.this.save_mtx(tmp_path / "state.mtx", self.mtx32, format)
+        loaded = self.this.load_mtx(tmp_path / "state.mtx")
+
+        assert loaded.dtype == self.dtype
+        assert np.array_equal(loaded, self.mtx32)
+
+    def test_mtx_hermitian(self, tmp_path: Path) -> None:
+        """Validate upper triangle of hermitian matrix is filled on load."""
+
+        (tmp_path / "state.mtx").write_text(
+            "%%MatrixMarket matrix coordinate complex hermitian\n"
+            "% comment\n"
+            "2 2 3\n"
+            "1 1 0.5 0\n"
+            "2 1 0.25 0.5\n"
+            "2 2 0.5 0\n"
+        )
+        loaded = self.this.load_mtx(tmp_path / "state.mtx")
+
+        assert np.array_equal(
+            loaded, np.array([[0.5, 0.25 - 0.5j], [0.25 + 0.5j, 0.5]])
+        )
+
+    @pytest.mark.parametrize(
+        ("content", "error"),
+        [
+            ("2 2\n1 0\n0 0\n0 0\n1 0\n", ValueError),
+            ("%%MatrixMarket matrix array complex\n2 2\n", ValueError),
+            ("%%MatrixMarket matrix array complex general\n2 3\n", ValueError),
+            ("%%MatrixMarket matrix array complex general\n2 2\n1 0\n", ValueError),
+            (
+                "%%MatrixMarket matrix coordinate pattern general\n2 2 1\n1 1\n",
+                TypeError,
+            ),
+            ("%%MatrixMarket matrix array integer general\n1 1\n0.5\n", TypeError),
+        ],
+    )
+    def test_mtx_invalid(self, tmp_path: Path, content: str, error: type) -> None:
+        """Validate malformed Matrix Market files are rejected."""
+
+        (tmp_path / "state.mtx").write_text(content)
+        with pytest.raises(error):
+            self.this.load_mtx(tmp_path / "state.mtx")
+
+    def test_mtx_save_non_square(self, tmp_path: Path) -> None:
+        """Validate only square matrices can be saved."""
+
+        with pytest.raises(ValueError):
+            self.this.save_mtx(tmp_path / "state.mtx", self.mtx32[:, :16])
+
     def test_noop(self) -> None:
         self.this.noop()
 
@@ -213,6 +381,12 @@ class TestComplex128(ValidateConformance):
     dtype = np.complex128
 
 
+class TestComplex64(ValidateConformance):
+    this = rust_backend.complex64
+    reference = numpy_c64
+    dtype = np.complex64
+
+
 class TestBackendClass:
     def get_backend_instance(self) -> BackendBase:
         return rust_backend.complex128.NaiveRustBackendF64(
@@ -223,13 +397,116 @@ class TestBackendClass:
             1.0,
         )
 
+    def get_entangled_state(self, depth: int = 2, quantity: int = 5) -> np.ndarray:
+        """GHZ state of `quantity` subsystems of dimension `depth`."""
+        size = depth**quantity
+        vector = np.zeros(size, dtype=np.complex128)
+        vector[:: (size - 1) // (depth - 1)] = 1 / np.sqrt(depth)
+        return np.outer(vector, vector.conj())
+
+    def assert_distance_decreases(self, instance: BackendBase) -> None:
+        corrections = instance.get_corrections()
+        assert len(corrections) > 0
+        assert corrections[-1][2] < corrections[0][2]
+
+    def assert_conforms_to_reference(
+        self, configure: Callable[[BackendBase], None]
+    ) -> None:
+        """Compare mean distance reached by this and NumPy backend, both configured
+        with `configure` and run on 3 qubit GHZ state."""
+        reference_class = cssfinder_backend_numpy.export_backend()[
+            ("numpy", Precision.DOUBLE)
+        ]
+
+        def distances(create: Callable[[int], BackendBase]) -> np.ndarray:
+            distances = []
+            for seed in range(16):
+                # NumPy backend draws from global generator.
+                np.random.seed(seed)
+                instance = create(seed)
+                configure(instance)
+                instance.run_epoch(1000, 0)
+                distances.append(instance.get_corrections()[-1][2])
+            return np.array(distances)
+
+        this = distances(
+            lambda seed: rust_backend.complex128.NaiveRustBackendF64(
+                self.get_entangled_state(2, 3), 2, 3, AlgoMode.FSnQd, 0.5, seed=seed
+            )
+        )
+        reference = distances(
+            lambda _: reference_class(
+                self.get_entangled_state(2, 3), 2, 3, AlgoMode.FSnQd, 0.5
+            )
+        )
+        # Runs are seeded so result is reproducible, still means are compared within
+        # few standard errors, as both backends draw different random numbers.
+        error = np.sqrt(
+            this.var(ddof=1) / len(this) + reference.var(ddof=1) / len(reference)
+        )
+        assert abs(this.mean() - reference.mean()) < 4 * error
+
     def test_backend_set_symmetries(self) -> None:
         instance = self.get_backend_instance()
         instance.set_symmetries([[np.identity(32).astype(np.complex128)]])
 
+    def test_backend_set_symmetries_group(self) -> None:
+        instance = rust_backend.complex128.NaiveRustBackendF64(
+            self.get_entangled_state(), 2, 5, AlgoMode.FSnQd, 0.5, seed=0
+        )
+        reversal = np.identity(32)[::-1].astype(np.complex128)
+        instance.set_symmetries([[reversal]], "generators")
+        instance.run_epoch(256, 0)
+
+        assert len(instance.get_corrections()) > 0
+        state = instance.get_state()
+        assert np.allclose(reversal @ state @ reversal.conj().T, state)
+
+    def test_backend_set_symmetries_conforms(self) -> None:
+        reversal = np.identity(8)[::-1].astype(np.complex128)
+        self.assert_conforms_to_reference(
+            lambda instance: instance.set_symmetries([[reversal]])
+        )
+
+    def test_backend_set_symmetries_not_unitary(self) -> None:
+        instance = self.get_backend_instance()
+        with pytest.raises(ValueError):
+            instance.set_symmetries(
+                [[2 * np.identity(32).astype(np.complex128)]], "generators"
+            )
+
     def test_backend_set_projection(self) -> None:
+        """Validate only projected candidates are mixed into the state, so its part
+        outside of projection subspace only shrinks."""
+        instance = rust_backend.complex128.NaiveRustBackendF64(
+            self.get_entangled_state(), 2, 5, AlgoMode.FSnQd, 0.5, seed=0
+        )
+        projection = np.zeros((32, 32), dtype=np.complex128)
+        projection[0, 0] = projection[31, 31] = 1
+        initial = instance.get_state()
+        instance.set_projection(projection)
+        instance.run_epoch(256, 0)
+
+        assert len(instance.get_corrections()) > 0
+        state = instance.get_state()
+        scale = state[1, 1] / initial[1, 1]
+        assert scale.real < 1
+        assert np.allclose(
+            state - projection @ state @ projection,
+            scale * (initial - projection @ initial @ projection),
+        )
+
+    def test_backend_set_projection_conforms(self) -> None:
+        projection = np.zeros((8, 8), dtype=np.complex128)
+        projection[0, 0] = projection[7, 7] = 1
+        self.assert_conforms_to_reference(
+            lambda instance: instance.set_projection(projection)
+        )
+
+    def test_backend_set_projection_shape_mismatch(self) -> None:
         instance = self.get_backend_instance()
-        instance.set_projection(np.identity(32).astype(np.complex128))
+        with pytest.raises(ValueError):
+            instance.set_projection(np.identity(16).astype(np.complex128))
 
     def test_backend_get_state(self) -> None:
         instance = self.get_backend_instance()
@@ -242,3 +519,247 @@ class TestBackendClass:
     def test_backend_run_epoch(self) -> None:
         instance = self.get_backend_instance()
         instance.run_epoch(16, 0)
+
+    def test_backend_run_epoch_sbipa(self) -> None:
+        instance = rust_backend.complex128.NaiveRustBackendF64(
+            self.get_entangled_state(),
+            4,
+            8,
+            AlgoMode.SBiPa,
+            1.0,
+            seed=0,
+        )
+        instance.run_epoch(256, 0)
+        self.assert_distance_decreases(instance)
+
+    def test_backend_run_epoch_g3pae3qd(self) -> None:
+        instance = rust_backend.complex128.NaiveRustBackendF64(
+            self.get_entangled_state(3, 3),
+            3,
+            3,
+            AlgoMode.G3PaE3qD,
+            1.0,
+            seed=0,
+        )
+        instance.run_epoch(256, 0)
+        self.assert_distance_decreases(instance)
+
+    def test_backend_run_epoch_g4pae3qd(self) -> None:
+        instance = rust_backend.complex128.NaiveRustBackendF64(
+            self.get_entangled_state(2, 4),
+            2,
+            4,
+            AlgoMode.G4PaE3qD,
+            1.0,
+            seed=0,
+        )
+        instance.run_epoch(256, 0)
+        self.assert_distance_decreases(instance)
+
+    def test_backend_heterogeneous_dimensions(self) -> None:
+        instance = rust_backend.complex128.NaiveRustBackendF64(
+            np.identity(24).astype(np.complex128),
+            0,
+            0,
+            AlgoMode.FSnQd,
+            1.0,
+            dimensions=[2, 3, 4],
+        )
+        assert instance.get_dimensions() == [2, 3, 4]
+        instance.run_epoch(16, 0)
+
+    def test_backend_seed_reproducible(self) -> None:
+        def run(seed: int) -> list:
+            instance = rust_backend.complex128.NaiveRustBackendF64(
+                self.get_entangled_state(),
+                2,
+                5,
+                AlgoMode.FSnQd,
+                0.5,
+                seed=seed,
+            )
+            instance.run_epoch(64, 0)
+            return instance.get_corrections()
+
+        corrections = run(42)
+        assert len(corrections) > 0
+        assert corrections == run(42)
+        assert corrections != run(43)
+
+    def test_backend_threads_reproducible(self) -> None:
+        def run(seed: int) -> list:
+            instance = rust_backend.complex128.NaiveRustBackendF64(
+                self.get_entangled_state(),
+                2,
+                5,
+                AlgoMode.FSnQd,
+                0.5,
+                seed=seed,
+            )
+            instance.set_threads(4)
+            assert instance.get_threads() == 4
+            instance.run_epoch(2048, 0)
+            return instance.get_corrections()
+
+        corrections = run(42)
+        assert len(corrections) > 0
+        assert corrections == run(42)
+        assert corrections != run(43)
+
+    def test_backend_checkpoint_resume(self, tmp_path: Path) -> None:
+        def create() -> rust_backend.complex128.NaiveRustBackendF64:
+            instance = rust_backend.complex128.NaiveRustBackendF64(
+                self.get_entangled_state(),
+                2,
+                5,
+                AlgoMode.FSnQd,
+                0.5,
+                seed=42,
+            )
+            instance.set_projection(np.identity(32).astype(np.complex128))
+            return instance
+
+        uninterrupted = create()
+        uninterrupted.run_epoch(64, 0)
+        uninterrupted.run_epoch(64, 1)
+
+        interrupted = create()
+        interrupted.run_epoch(64, 0)
+        interrupted.save_checkpoint(tmp_path / "backend.ckpt")
+        resumed = rust_backend.complex128.NaiveRustBackendF64.load_checkpoint(
+            tmp_path / "backend.ckpt"
+        )
+        resumed.run_epoch(64, 1)
+
+        assert resumed.get_corrections() == uninterrupted.get_corrections()
+        assert np.array_equal(resumed.get_state(), uninterrupted.get_state())
+
+    def test_backend_checkpoint_precision_mismatch(self, tmp_path: Path) -> None:
+        instance = self.get_backend_instance()
+        instance.save_checkpoint(tmp_path / "backend.ckpt")
+        with pytest.raises(ValueError):
+            rust_backend.complex64.NaiveRustBackendF32.load_checkpoint(
+                tmp_path / "backend.ckpt"
+            )
+
+    def test_backend_pickle(self) -> None:
+        instance = rust_backend.complex128.NaiveRustBackendF64(
+            self.get_entangled_state(), 2, 5, AlgoMode.FSnQd, 0.5
+        )
+        instance.set_symmetries([[np.identity(32).astype(np.complex128)]])
+        instance.set_projection(np.identity(32).astype(np.complex128))
+        instance.run_epoch(16, 0)
+
+        restored = pickle.loads(pickle.dumps(instance))
+        assert restored.get_corrections() == instance.get_corrections()
+
+        instance.run_epoch(16, 1)
+        restored.run_epoch(16, 1)
+        assert restored.get_corrections() == instance.get_corrections()
+        assert np.array_equal(restored.get_state(), instance.get_state())
+
+    def test_backend_deepcopy(self) -> None:
+        instance = rust_backend.complex128.NaiveRustBackendF64(
+            self.get_entangled_state(), 2, 5, AlgoMode.FSnQd, 0.5
+        )
+        instance.run_epoch(16, 0)
+
+        duplicate = copy.deepcopy(instance)
+        instance.run_epoch(16, 1)
+        assert duplicate.get_corrections_count() < instance.get_corrections_count()
+
+        duplicate.run_epoch(16, 1)
+        assert duplicate.get_corrections() == instance.get_corrections()
+
+    def test_backend_progress_callback(self) -> None:
+        instance = rust_backend.complex128.NaiveRustBackendF64(
+            self.get_entangled_state(), 2, 5, AlgoMode.FSnQd, 0.5, seed=42
+        )
+        progress = []
+        instance.run_epoch(
+            64, 0, callback=lambda *args: progress.append(args), interval=16
+        )
+
+        assert [iteration for iteration, _, _ in progress] == [16, 32, 48, 64]
+        assert progress[-1][1] == instance.get_corrections_count()
+        assert progress[-1][2] == instance.get_corrections()[-1][2]
+
+    def test_backend_progress_callback_stop(self) -> None:
+        instance = rust_backend.complex128.NaiveRustBackendF64(
+            self.get_entangled_state(), 2, 5, AlgoMode.FSnQd, 0.5, seed=42
+        )
+        progress = []
+
+        def callback(iteration: int, count: int, distance: float) -> bool:
+            progress.append(iteration)
+            return iteration >= 32
+
+        instance.run_epoch(1024, 0, callback=callback, interval=16)
+        assert progress == [16, 32]
+        assert all(iteration <= 32 for iteration, _, _ in instance.get_corrections())
+
+    def test_backend_progress_callback_raises(self) -> None:
+        instance = self.get_backend_instance()
+
+        def callback(iteration: int, count: int, distance: float) -> None:
+            raise RuntimeError
+
+        with pytest.raises(RuntimeError):
+            instance.run_epoch(64, 0, callback=callback, interval=16)
+
+    def test_backend_progress_invalid_interval(self) -> None:
+        instance = self.get_backend_instance()
+        with pytest.raises(ValueError):
+            instance.run_epoch(64, 0, callback=lambda *_: None, interval=0)
+
+    def test_backend_keyboard_interrupt(self) -> None:
+        instance = rust_backend.complex128.NaiveRustBackendF64(
+            self.get_entangled_state(), 2, 5, AlgoMode.FSnQd, 0.5, seed=42
+        )
+        timer = threading.Timer(0.5, signal.raise_signal, (signal.SIGINT,))
+        timer.start()
+        try:
+            with pytest.raises(KeyboardInterrupt):
+                instance.run_epoch(2**40, 0)
+        finally:
+            timer.cancel()
+
+        corrections = instance.get_corrections()
+        assert len(corrections) == instance.get_corrections_count()
+        assert np.isfinite(instance.get_state()).all()
+        instance.run_epoch(16, 1)
+
+    def test_backend_set_threads_zero(self) -> None:
+        instance = self.get_backend_instance()
+        with pytest.raises(ValueError):
+            instance.set_threads(0)
+
+    def test_backend_invalid_dimensions(self) -> None:
+        with pytest.raises(ValueError):
+            rust_backend.complex128.NaiveRustBackendF64(
+                np.identity(32).astype(np.complex128),
+                2,
+                4,
+                AlgoMode.FSnQd,
+                1.0,
+            )
+
+    def test_backend_invalid_mode(self) -> None:
+        with pytest.raises(TypeError):
+            rust_backend.complex128.NaiveRustBackendF64(
+                np.identity(32).astype(np.complex128),
+                2,
+                5,
+                "FSnQd",
+                1.0,
+            )
+
+    def test_backend_run_epoch_concurrent(self) -> None:
+        instances = [self.get_backend_instance() for _ in range(4)]
+
+        with ThreadPoolExecutor(max_workers=len(instances)) as executor:
+            futures = [
+                executor.submit(instance.run_epoch, 16, 0) for instance in instances
+            ]
+            for future in futures:
+                future.result()